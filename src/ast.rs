@@ -1,5 +1,8 @@
+use crate::document::for_each_text_unit_mut;
+use crate::metadata;
 use indexmap::IndexSet;
-use pulldown_cmark::{Event, OffsetIter, Parser, Tag};
+use pulldown_cmark::{Event, OffsetIter, Options, Parser, Tag};
+use std::collections::HashMap;
 use std::ops::Range;
 use unicase::UniCase;
 
@@ -8,6 +11,7 @@ use unicase::UniCase;
  *
  * Identified keywords are added to a set separate from the ast during parsing.
  * The variant of supported markdown is CommonMark.
+ * The markdown text may be preceded by a front matter header, see below.
  * All elements of the AST are in order of appearance in the original document.
  *
  * The supported subset of markdown is:
@@ -15,8 +19,12 @@ use unicase::UniCase;
  * - paragraphs
  * - horizontal rule
  * - lists (recursive, ordered or not, specific)
+ * - block quotes, containing other blocks but not headers: boxed text to read aloud
+ * - code blocks, kept verbatim and possibly interpreted as data, see the code module
+ * - tables (GitHub extension), with one line of text by cell
  * - strong tags in any inline: non-semantic highlighting, conserved in output
  * - emphasis tags in any inline: indicate a keyword, removed from output
+ * - secret content, see below
  * Restrictions:
  * - strong/emphasis tags cannot be multiline (not used, and not willing to support).
  *
@@ -25,8 +33,59 @@ use unicase::UniCase;
  * Links are not used for keyword definition as they have complex cases to handle.
  */
 
-/// Root of a markdown document. Equivalent to a level-0 section with no title.
-pub type Document = SectionContent;
+/// Root of a markdown document.
+/// Content is equivalent to a level-0 section with no title.
+#[derive(Debug)]
+pub struct Document {
+    pub front_matter: FrontMatter,
+    pub content: SectionContent,
+    /// Sources concatenated into this document, in order.
+    pub sources: Vec<SourceSpan>,
+}
+
+/// Named source text, like a file.
+pub struct Source {
+    pub name: String,
+    pub text: String,
+}
+
+/// Part of the document text coming from one source.
+#[derive(Debug)]
+pub struct SourceSpan {
+    pub name: String,
+    /// Line of the span start in the document text, starting from 1.
+    pub first_line: usize,
+    /// Line of the span start in the source, starting from 1. Differs from 1 due to front matter.
+    pub source_first_line: usize,
+}
+
+/// Parsing behavior for unsupported markdown elements and secret content.
+#[derive(Debug, Default, Clone)]
+pub struct ParseOptions {
+    /// Skip unsupported blocks and keep text of unsupported inline elements, with a warning.
+    /// By default they generate an error.
+    pub lenient: bool,
+    /// Remove content not visible to this audience, see `crate::secret`.
+    pub audience: Option<String>,
+}
+
+/// Metadata of the document, all optional.
+/// For multiple sources, single values are taken from the first source defining them, and lists are merged.
+#[derive(Debug, Default)]
+pub struct FrontMatter {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub date: Option<String>,
+    pub tags: Vec<String>,
+    /// Keywords declared in advance, even without explicit occurrences.
+    pub keywords: Vec<String>,
+    /// Alternative names of keywords, as (keyword, aliases) pairs.
+    pub aliases: Vec<(String, Vec<String>)>,
+    pub language: Option<String>,
+    /// Marks all the content of its source as secret, with the audiences allowed to see it.
+    /// Not merged between sources.
+    pub secret: Option<Vec<String>>,
+}
 
 #[derive(Debug)]
 pub struct Section {
@@ -45,6 +104,36 @@ pub enum BlockElement {
     Paragraph(Vec<InlineElement>),
     Rule,
     List(List),
+    Quote(Vec<BlockElement>),
+    /// Verbatim text, with the first word of the info string of fenced blocks.
+    Code {
+        lang: Option<String>,
+        text: String,
+        /// Line of the start of the block in the source text, starting from 1.
+        line: usize,
+        /// Audiences allowed to see the block if it is secret.
+        secret: Option<Vec<String>>,
+    },
+    Table(Table),
+}
+
+/// Cells are text units, with one inline element or none if empty.
+#[derive(Debug)]
+pub struct Table {
+    /// Alignment of each column.
+    pub alignments: Vec<Alignment>,
+    pub header: Vec<TableCell>,
+    pub rows: Vec<Vec<TableCell>>,
+}
+
+pub type TableCell = Vec<InlineElement>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alignment {
+    None,
+    Left,
+    Center,
+    Right,
 }
 
 #[derive(Debug)]
@@ -64,6 +153,8 @@ pub struct ListItem {
 pub struct InlineElement {
     /// Unique index
     pub index: InlineIndex,
+    /// Line of the start of the element in the source text, starting from 1.
+    pub line: usize,
     /// Raw string content without any formatting
     pub string: String,
     /// List of tagged ranges (order FIXME)
@@ -82,6 +173,9 @@ pub enum InlineTag {
     ExplicitKeyword(usize),
     /// Implicit keyword occurrence, found by search of known keywords.
     ImplicitKeyword(usize),
+    /// Secret content, visible to the game master and the given audiences.
+    /// May overlap with other tags, but is properly nested with them.
+    Secret(Vec<String>),
 }
 
 /******************************************************************************
@@ -104,53 +198,114 @@ impl SectionContent {
     }
 }
 
+impl Document {
+    /// Source name and line in the source, from a line of the document text.
+    pub fn source_location(&self, line: usize) -> (&str, usize) {
+        let span = self
+            .sources
+            .iter()
+            .rev()
+            .find(|span| span.first_line <= line)
+            .expect("Line before first source");
+        (&span.name, line - span.first_line + span.source_first_line)
+    }
+}
+
 /******************************************************************************
  * Parsing.
  *
+ * Multiple sources are parsed as the concatenation of their markdown text, each ended by a blank line.
+ * Each source can have its own front matter.
+ *
  * Parsing error behavior:
- * - normal error for unsupported parts of markdown.
+ * - normal error for unsupported parts of markdown, or a warning if lenient.
  * - panic if the Parser returns unexpected events: unclosed tags, etc.
  */
 
 /// Closure-like struct to allow use of recursive functions for parsing.
 struct ParsingState<'s, 'k> {
+    text: &'s str,
+    options: &'k ParseOptions,
     iter: OffsetIter<'s>,
     keywords: &'k mut KeywordSet,
+    /// Explicit occurrences of aliases are recorded for their keyword.
+    aliases: &'k HashMap<UniCase<String>, usize>,
     inline_element_count: usize,
+    /// Last (offset, line) pair computed, to avoid rescanning text from the start.
+    line_cursor: (usize, usize),
+    warnings: Vec<Error>,
 }
 
 /// Return type for events consumed by not processed by a parsing function.
 /// Returned by functions that require an unexpected event to stop parsing (inline, sub_section).
 type Consumed<'s> = Option<(Event<'s>, usize)>;
 
-/// Error message and indicative offset.
+/// Error (or warning) message and indicative offset.
 type Error = (String, usize);
 
 impl<'s, 'k> ParsingState<'s, 'k> {
-    fn new(text: &'s str, keywords: &'k mut KeywordSet) -> Self {
+    fn new(
+        text: &'s str,
+        options: &'k ParseOptions,
+        keywords: &'k mut KeywordSet,
+        aliases: &'k HashMap<UniCase<String>, usize>,
+    ) -> Self {
         Self {
-            iter: Parser::new(text).into_offset_iter(),
+            text,
+            options,
+            iter: Parser::new_ext(text, Options::ENABLE_TABLES).into_offset_iter(),
             keywords,
+            aliases,
             inline_element_count: 0,
+            line_cursor: (0, 0),
+            warnings: Vec::new(),
         }
     }
 
+    /// Line number (from 0) of an offset. Efficient for increasing offsets.
+    fn line_of(&mut self, offset: usize) -> usize {
+        let (cursor_offset, cursor_line) = self.line_cursor;
+        let line = if offset >= cursor_offset {
+            cursor_line + line_number_of_offset(&self.text[cursor_offset..], offset - cursor_offset)
+        } else {
+            line_number_of_offset(self.text, offset)
+        };
+        self.line_cursor = (offset, line);
+        line
+    }
+
     fn consume(&mut self) -> Consumed<'s> {
         self.iter.next().map(|(e, r)| (e, r.start))
     }
 
+    /// Skip an unsupported element from start tag (already consumed) to end tag (included).
+    fn skip_element(&mut self, start: (Tag<'s>, usize)) {
+        let (tag, offset) = start;
+        self.warnings
+            .push((format!("Skipped unsupported element: {:?}", tag), offset));
+        let mut depth = 1;
+        while depth > 0 {
+            match self.consume().expect("Unclosed element").0 {
+                Event::Start(_) => depth += 1,
+                Event::End(_) => depth -= 1,
+                _ => (),
+            }
+        }
+    }
+
     /// Parse one markdown document. Consumes the parsing state as the iterator is now empty.
-    fn parse_document(mut self) -> Result<Document, Error> {
+    /// Also returns warnings.
+    fn parse_document(mut self) -> Result<(SectionContent, Vec<Error>), Error> {
         let (root_content, next) = self.parse_section_content_at_level(0)?;
         match next {
-            None => Ok(root_content),
+            None => Ok((root_content, self.warnings)),
             Some((e, o)) => Err((format!("Unexpected element: {:?}", e), o)),
         }
     }
 
     /// Parse sub_section (header + content) from start tag (already consumed) to end of sub_section.
     fn parse_section_of_level(&mut self, level: i32) -> Result<(Section, Consumed<'s>), Error> {
-        let title = match self.parse_inline()? {
+        let mut title = match self.parse_inline()? {
             (Some(string), Some((Event::End(Tag::Header(n)), _))) => {
                 assert_eq!(n, level);
                 string
@@ -164,6 +319,11 @@ impl<'s, 'k> ParsingState<'s, 'k> {
             (None, _) => panic!("Header without title"),
             (_, None) => panic!("Unclosed header"),
         };
+        if let Some((audiences, start)) = secret_suffix(&title.string) {
+            let len = title.string.len();
+            remove_text(&mut title, start..len);
+            mark_secret(std::slice::from_mut(&mut title), audiences)
+        }
         let (content, next) = self.parse_section_content_at_level(level)?;
         Ok((Section { title, content }, next))
     }
@@ -231,6 +391,29 @@ impl<'s, 'k> ParsingState<'s, 'k> {
             Some((Event::Start(Tag::List(start_i)), _)) => {
                 Ok(BlockElement::List(self.parse_list(start_i.is_some())?))
             }
+            Some((Event::Start(Tag::BlockQuote), _)) => {
+                Ok(BlockElement::Quote(self.parse_quote()?))
+            }
+            Some((Event::Start(Tag::Table(alignments)), _)) => {
+                Ok(BlockElement::Table(self.parse_table(alignments)?))
+            }
+            Some((Event::Start(Tag::CodeBlock(info)), o)) => {
+                let (lang, marker) = info.split_at(info.find(SECRET_MARKER).unwrap_or(info.len()));
+                let lang = lang.split_whitespace().next().map(String::from);
+                let secret = secret_marker(marker.trim_end()).map(|(audiences, _)| audiences);
+                let line = self.line_of(o) + 1;
+                let text = self.parse_code();
+                Ok(BlockElement::Code {
+                    lang,
+                    text,
+                    line,
+                    secret,
+                })
+            }
+            Some((Event::Start(tag), o)) if self.options.lenient && is_unsupported_block(&tag) => {
+                self.skip_element((tag, o));
+                return self.try_parse_block();
+            }
             next => Err(next),
         })
     }
@@ -241,13 +424,89 @@ impl<'s, 'k> ParsingState<'s, 'k> {
         let next_event = next.expect("Unclosed paragraph");
         match next_event {
             (Event::End(Tag::Paragraph), _) => {
-                assert!(inline_sequence.len() > 0);
-                Ok(inline_sequence)
+                assert!(!inline_sequence.is_empty());
+                Ok(self.secret_prefix(inline_sequence))
             }
             (e, o) => Err((format!("Parsing paragraph: unexpected {:?}", e), o)),
         }
     }
 
+    /// Parse block quote from start tag (already consumed) to end tag (included).
+    fn parse_quote(&mut self) -> Result<Vec<BlockElement>, Error> {
+        let mut blocks = Vec::new();
+        loop {
+            match self.try_parse_block()? {
+                Ok(block) => blocks.push(block),
+                Err(Some((Event::End(Tag::BlockQuote), _))) => return Ok(blocks),
+                Err(Some((Event::Start(tag @ Tag::Header(_)), o))) if self.options.lenient => {
+                    self.skip_element((tag, o))
+                }
+                Err(Some((e, o))) => return Err((format!("Parsing quote: unexpected {:?}", e), o)),
+                Err(None) => panic!("Unclosed quote"),
+            }
+        }
+    }
+
+    /// Parse code block text from start tag (already consumed) to end tag (included).
+    fn parse_code(&mut self) -> String {
+        let mut text = String::new();
+        loop {
+            match self.consume().expect("Unclosed code block").0 {
+                Event::Text(s) => text.push_str(&s),
+                Event::End(Tag::CodeBlock(_)) => return text,
+                e => panic!("Expected code block text: {:?}", e),
+            }
+        }
+    }
+
+    /// Parse table from start tag (already consumed) to end tag (included).
+    fn parse_table(&mut self, alignments: Vec<pulldown_cmark::Alignment>) -> Result<Table, Error> {
+        let alignments = (alignments.into_iter())
+            .map(|alignment| match alignment {
+                pulldown_cmark::Alignment::None => Alignment::None,
+                pulldown_cmark::Alignment::Left => Alignment::Left,
+                pulldown_cmark::Alignment::Center => Alignment::Center,
+                pulldown_cmark::Alignment::Right => Alignment::Right,
+            })
+            .collect();
+        let mut header = Vec::new();
+        let mut rows = Vec::new();
+        loop {
+            match self.consume().expect("Unclosed table").0 {
+                Event::Start(Tag::TableHead) => header = self.parse_table_row()?,
+                Event::Start(Tag::TableRow) => rows.push(self.parse_table_row()?),
+                Event::End(Tag::Table(_)) => {
+                    return Ok(Table {
+                        alignments,
+                        header,
+                        rows,
+                    })
+                }
+                e => panic!("Expected table rows: {:?}", e),
+            }
+        }
+    }
+    /// Parse cells of a table head or row, from start tag (already consumed) to end tag (included).
+    fn parse_table_row(&mut self) -> Result<Vec<TableCell>, Error> {
+        let mut cells = Vec::new();
+        loop {
+            match self.consume().expect("Unclosed table row").0 {
+                Event::Start(Tag::TableCell) => {
+                    let (inline, next) = self.parse_inline()?;
+                    match next.expect("Unclosed table cell") {
+                        (Event::End(Tag::TableCell), _) => (),
+                        (e, o) => {
+                            return Err((format!("Parsing table cell: unexpected {:?}", e), o))
+                        }
+                    }
+                    cells.push(self.secret_prefix(inline.into_iter().collect()))
+                }
+                Event::End(Tag::TableHead) | Event::End(Tag::TableRow) => return Ok(cells),
+                e => panic!("Expected table cells: {:?}", e),
+            }
+        }
+    }
+
     /// Parse list from start tag (already consumed) to end tag (included).
     fn parse_list(&mut self, ordered: bool) -> Result<List, Error> {
         let mut items: Vec<ListItem> = Vec::new();
@@ -261,8 +520,9 @@ impl<'s, 'k> ParsingState<'s, 'k> {
     }
     fn parse_list_item(&mut self) -> Result<ListItem, Error> {
         let (text_content, next) = self.parse_inline_sequence()?;
+        let text_content = self.secret_prefix(text_content);
         let next_event = next.expect("Unclosed list item");
-        if text_content.len() == 0 {
+        if text_content.is_empty() {
             return Err(("List item with empty text".into(), next_event.1));
         }
         let sub_list = match next_event {
@@ -298,6 +558,30 @@ impl<'s, 'k> ParsingState<'s, 'k> {
         }
     }
 
+    /// Mark a text unit as secret if it starts with the secret marker, removing it.
+    /// The unit must be the last parsed one. If the marker is alone on its line, this line is removed.
+    fn secret_prefix(&mut self, mut inlines: Vec<InlineElement>) -> Vec<InlineElement> {
+        let marker = inlines
+            .first()
+            .and_then(|first| secret_marker(&first.string));
+        let (audiences, length) = match marker {
+            Some(marker) => marker,
+            None => return inlines,
+        };
+        let first = &mut inlines[0];
+        let end = first.string.len() - first.string[length..].trim_start().len();
+        remove_text(first, 0..end);
+        if first.string.is_empty() && inlines.len() > 1 {
+            inlines.remove(0);
+            for inline in &mut inlines {
+                inline.index -= 1
+            }
+            self.inline_element_count -= 1
+        }
+        mark_secret(&mut inlines, audiences);
+        inlines
+    }
+
     /// Parse one inline text unit (with emphasis / strong), may be empty.
     /// Will panic in case of structural errors slipping past the markdown parser.
     fn parse_inline(&mut self) -> Result<(Option<InlineElement>, Consumed<'s>), Error> {
@@ -307,11 +591,15 @@ impl<'s, 'k> ParsingState<'s, 'k> {
         let mut tags: Vec<(Range<usize>, InlineTag)> = Vec::new();
         let mut strong_start: Option<usize> = None;
         let mut emphasis_start: Option<usize> = None;
+        let mut start_offset: Option<usize> = None;
         // Parse all inline elements
         let next = loop {
             match self.consume() {
-                Some((Event::Text(s), _)) => match &mut string {
-                    None => string = Some(s.into_string()),
+                Some((Event::Text(s), o)) => match &mut string {
+                    None => {
+                        string = Some(s.into_string());
+                        start_offset = Some(o)
+                    }
                     Some(string) => string.push_str(&s),
                 },
                 // Emphasis
@@ -329,7 +617,11 @@ impl<'s, 'k> ParsingState<'s, 'k> {
                     let string = string.as_ref().expect("Empty emphasis block");
                     let end = string.len();
                     let string = string[start..end].to_string();
-                    let (index, _) = self.keywords.insert_full(UniCase::new(string));
+                    let string = UniCase::new(string);
+                    let index = match self.aliases.get(&string) {
+                        Some(index) => *index,
+                        None => self.keywords.insert_full(string).0,
+                    };
                     tags.push((start..end, InlineTag::ExplicitKeyword(index)))
                 }
                 // Strong
@@ -346,22 +638,240 @@ impl<'s, 'k> ParsingState<'s, 'k> {
                     let end = string.len();
                     tags.push((start..end, InlineTag::Highlight))
                 }
+                // Unsupported inline elements in lenient mode: keep text, ignore formatting.
+                Some((Event::Code(s), o)) if self.options.lenient => {
+                    let warning = format!("Inline code kept as text: {}", s);
+                    self.warnings.push((warning, o));
+                    match &mut string {
+                        None => {
+                            string = Some(s.into_string());
+                            start_offset = Some(o)
+                        }
+                        Some(string) => string.push_str(&s),
+                    }
+                }
+                Some((Event::Start(tag), o))
+                    if self.options.lenient && is_unsupported_inline(&tag) =>
+                {
+                    let warning = format!("Ignored unsupported formatting: {:?}", tag);
+                    self.warnings.push((warning, o))
+                }
+                Some((Event::End(tag), _))
+                    if self.options.lenient && is_unsupported_inline(&tag) => {}
+                Some((Event::InlineHtml(s), o)) if self.options.lenient => self
+                    .warnings
+                    .push((format!("Skipped inline html: {}", s), o)),
                 next => break next,
             }
         };
-        let inline = string.map(|string| {
-            let index = self.inline_element_count;
-            self.inline_element_count = self.inline_element_count + 1;
-            InlineElement {
-                index,
-                string,
-                tags,
+        let inline = match string {
+            None => None,
+            Some(mut string) => {
+                let offset = start_offset.unwrap();
+                add_secret_tags(&mut string, &mut tags).map_err(|msg| (msg, offset))?;
+                let index = self.inline_element_count;
+                self.inline_element_count += 1;
+                let line = self.line_of(offset) + 1;
+                Some(InlineElement {
+                    index,
+                    line,
+                    string,
+                    tags,
+                })
             }
-        });
+        };
         Ok((inline, next))
     }
 }
 
+fn is_unsupported_block(tag: &Tag) -> bool {
+    matches!(tag, Tag::HtmlBlock | Tag::FootnoteDefinition(_))
+}
+fn is_unsupported_inline(tag: &Tag) -> bool {
+    matches!(tag, Tag::Link(..) | Tag::Image(..) | Tag::Strikethrough)
+}
+
+/******************************************************************************
+ * Secret content.
+ *
+ * Content can be marked as secret, for instance notes meant for the game master only:
+ * - a range of an inline, between "||" delimiters: "The door ||is trapped||."
+ * - a paragraph, list item or table cell, with a "[secret]" prefix. Sub lists of a secret list item are not secret.
+ *   Inline ranges cannot be used in table cells, as "|" separates cells.
+ * - a section with its content, with a "[secret]" suffix on its header.
+ * - a code block, with the marker after the language of a fenced block: "```statblock [secret]".
+ *   Code blocks following an entirely secret paragraph are also secret, like "[secret] *Kwd* :".
+ * - a whole source, with "secret: true" in its front matter. Its front matter is then also secret.
+ * Secret content is visible to the game master only, unless audiences are given:
+ * "[secret: party, player:alice]" markers, also at the start of inline ranges, or "secret: [party]".
+ * Markers are removed from the text, and secret text is tagged with InlineTag::Secret.
+ * Secret units keep the secret ranges inside them, which may be more restricted.
+ * A secret inline range cannot cross the limits of an emphasis or strong tag.
+ */
+
+const SECRET_MARKER: &str = "[secret";
+const SECRET_DELIMITER: &str = "||";
+
+/// Parse a "[secret]" or "[secret: audience, ...]" marker at the start of text.
+/// Returns the audiences and the length of the marker.
+fn secret_marker(text: &str) -> Option<(Vec<String>, usize)> {
+    let rest = text.strip_prefix(SECRET_MARKER)?;
+    let end = rest.find(']')?;
+    let audiences = match rest[..end].strip_prefix(':') {
+        Some(list) => list
+            .split(',')
+            .map(str::trim)
+            .filter(|audience| !audience.is_empty())
+            .map(String::from)
+            .collect(),
+        None if end == 0 => Vec::new(),
+        None => return None,
+    };
+    Some((audiences, SECRET_MARKER.len() + end + 1))
+}
+
+/// Secret marker for the audiences, as parsed by `secret_marker`.
+pub fn format_secret_marker(audiences: &[String]) -> String {
+    match audiences.is_empty() {
+        true => format!("{}]", SECRET_MARKER),
+        false => format!("{}: {}]", SECRET_MARKER, audiences.join(", ")),
+    }
+}
+
+/// Parse a secret marker at the end of a header title.
+/// Returns the audiences and the end of the title text without the marker.
+fn secret_suffix(title: &str) -> Option<(Vec<String>, usize)> {
+    let title = title.trim_end();
+    let start = title.rfind(SECRET_MARKER)?;
+    match secret_marker(&title[start..]) {
+        Some((audiences, length)) if start + length == title.len() => {
+            Some((audiences, title[..start].trim_end().len()))
+        }
+        _ => None,
+    }
+}
+
+/// Replace "||" delimited ranges, with their optional secret marker, by secret tags.
+fn add_secret_tags(
+    string: &mut String,
+    tags: &mut Vec<(Range<usize>, InlineTag)>,
+) -> Result<(), String> {
+    let delimiters: Vec<usize> = string
+        .match_indices(SECRET_DELIMITER)
+        .map(|(i, _)| i)
+        .collect();
+    if delimiters.is_empty() {
+        return Ok(());
+    }
+    if delimiters.len() % 2 != 0 {
+        return Err("Unclosed secret range".into());
+    }
+    // Removed text: delimiters, and markers with the spaces after them
+    let mut removed: Vec<Range<usize>> = Vec::new();
+    let mut ranges = Vec::new();
+    for pair in delimiters.chunks(2) {
+        let (open, close) = (pair[0], pair[1]);
+        let mut start = open + SECRET_DELIMITER.len();
+        let audiences = match secret_marker(&string[start..close]) {
+            Some((audiences, length)) => {
+                start = close - string[start + length..close].trim_start().len();
+                audiences
+            }
+            None => Vec::new(),
+        };
+        removed.push(open..start);
+        removed.push(close..close + SECRET_DELIMITER.len());
+        ranges.push((start..close, audiences))
+    }
+    let shifted = |offset: usize| {
+        let before: usize = removed
+            .iter()
+            .map(|r| offset.min(r.end).saturating_sub(r.start))
+            .sum();
+        offset - before
+    };
+    for (range, _) in tags.iter_mut() {
+        *range = shifted(range.start)..shifted(range.end)
+    }
+    let crosses =
+        |a: &Range<usize>, b: &Range<usize>| a.start < b.start && b.start < a.end && a.end < b.end;
+    for (range, audiences) in ranges {
+        let secret = shifted(range.start)..shifted(range.end);
+        if tags
+            .iter()
+            .any(|(range, _)| crosses(range, &secret) || crosses(&secret, range))
+        {
+            return Err("Secret range crossing emphasis or strong".into());
+        }
+        tags.push((secret, InlineTag::Secret(audiences)))
+    }
+    for range in removed.iter().rev() {
+        string.replace_range(range.clone(), "")
+    }
+    Ok(())
+}
+
+/// Mark code blocks as secret if they are in a secret source, or follow an entirely secret paragraph.
+fn mark_secret_code(content: &mut SectionContent, secret_lines: &[(Range<usize>, Vec<String>)]) {
+    fn blocks_code(blocks: &mut [BlockElement], secret_lines: &[(Range<usize>, Vec<String>)]) {
+        for i in 0..blocks.len() {
+            let previous = match i.checked_sub(1).map(|i| &blocks[i]) {
+                Some(BlockElement::Paragraph(inlines)) => unit_secret(inlines).cloned(),
+                _ => None,
+            };
+            match &mut blocks[i] {
+                BlockElement::Code { line, secret, .. } if secret.is_none() => {
+                    let source = secret_lines.iter().find(|(lines, _)| lines.contains(line));
+                    *secret = source.map(|(_, audiences)| audiences.clone()).or(previous)
+                }
+                BlockElement::Quote(blocks) => blocks_code(blocks, secret_lines),
+                _ => (),
+            }
+        }
+    }
+    blocks_code(&mut content.blocks, secret_lines);
+    for section in &mut content.sub_sections {
+        mark_secret_code(&mut section.content, secret_lines)
+    }
+}
+
+/// Audiences of a text unit whose inlines are all entirely secret, for the same audiences.
+pub fn unit_secret(inlines: &[InlineElement]) -> Option<&Vec<String>> {
+    let audiences = whole_secret(inlines.first()?)?;
+    let same = |inline| whole_secret(inline) == Some(audiences);
+    Some(audiences).filter(|_| inlines.iter().all(same))
+}
+
+/// Audiences of the first tag making the whole inline secret.
+pub fn whole_secret(inline: &InlineElement) -> Option<&Vec<String>> {
+    inline.tags.iter().find_map(|(range, tag)| match tag {
+        InlineTag::Secret(audiences) if *range == (0..inline.string.len()) => Some(audiences),
+        _ => None,
+    })
+}
+
+/// Tag the whole text of inlines as secret for the audiences.
+pub fn mark_secret(inlines: &mut [InlineElement], audiences: Vec<String>) {
+    for inline in inlines {
+        let range = 0..inline.string.len();
+        inline
+            .tags
+            .push((range, InlineTag::Secret(audiences.clone())))
+    }
+}
+
+/// Remove a range of text without tags from an inline.
+fn remove_text(inline: &mut InlineElement, removed: Range<usize>) {
+    let shifted = |offset: usize| match offset {
+        o if o >= removed.end => o - removed.len(),
+        o => o.min(removed.start),
+    };
+    for (range, _) in inline.tags.iter_mut() {
+        *range = shifted(range.start)..shifted(range.end)
+    }
+    inline.string.replace_range(removed, "");
+}
+
 /// Return the line number at a given offset, starting from 0.
 fn line_number_of_offset(text: &str, offset: usize) -> usize {
     text.bytes().take(offset).filter(|b| *b == b'\n').count()
@@ -370,19 +880,237 @@ fn line_number_of_offset(text: &str, offset: usize) -> usize {
 /// Set of keywords: indexed, and case insensitive.
 pub type KeywordSet = IndexSet<UniCase<String>>;
 
-/// Parse a single document from a string. Also returns the set of keywords.
+/// Parse a document from sources. Also returns the set of keywords, and warnings.
 /// The returned AST only contains explicit keyword occurrences.
 /// The AST should not be modified, as it might break internal indexation.
 /// This is not restricted by the interface for simplicity.
-pub fn parse(text: &str) -> Result<(Document, KeywordSet), String> {
+pub fn parse_sources(
+    sources: &[Source],
+    options: &ParseOptions,
+) -> Result<(Document, KeywordSet, Vec<String>), String> {
+    // Split front matters, and concatenate markdown parts
+    let mut front_matter = FrontMatter::default();
+    let mut text = String::new();
+    let mut spans = Vec::new();
+    let mut secret_lines = Vec::new();
+    let mut line_count = 0;
+    for source in sources {
+        let (source_front_matter, body_offset) =
+            parse_front_matter(&source.text).map_err(|(msg, offset)| {
+                let line = line_number_of_offset(&source.text, offset) + 1;
+                format!("{}:{}: {}", source.name, line, msg)
+            })?;
+        let secret = source_front_matter.secret.clone();
+        // Front matter of a secret source is as secret as its content
+        let visible = match (&options.audience, &secret) {
+            (Some(audience), Some(audiences)) => crate::secret::is_visible(audience, audiences),
+            _ => true,
+        };
+        if visible {
+            front_matter.merge(source_front_matter)
+        }
+        spans.push(SourceSpan {
+            name: source.name.clone(),
+            first_line: line_count + 1,
+            source_first_line: line_number_of_offset(&source.text, body_offset) + 1,
+        });
+        let body = &source.text[body_offset..];
+        text.push_str(body);
+        if !body.ends_with('\n') {
+            text.push('\n')
+        }
+        text.push('\n');
+        let first_line = line_count + 1;
+        line_count = line_number_of_offset(&text, text.len());
+        if let Some(audiences) = secret {
+            secret_lines.push((first_line..line_count + 1, audiences))
+        }
+    }
+    let mut document = Document {
+        front_matter,
+        content: SectionContent {
+            blocks: Vec::new(),
+            sub_sections: Vec::new(),
+        },
+        sources: spans,
+    };
+    let located = |document: &Document, (msg, offset): Error| {
+        let (name, line) = document.source_location(line_number_of_offset(&text, offset) + 1);
+        format!("{}:{}: {}", name, line, msg)
+    };
+
+    // Declared keywords and alias targets have the first indexes
     let mut keywords = IndexSet::new();
-    match ParsingState::new(text, &mut keywords).parse_document() {
-        Ok(document) => Ok((document, keywords)),
-        Err((msg, offset)) => Err(format!(
-            "At line {}: {}",
-            line_number_of_offset(text, offset) + 1,
-            msg
-        )),
+    let mut aliases = HashMap::new();
+    for keyword in &document.front_matter.keywords {
+        keywords.insert(UniCase::new(keyword.clone()));
+    }
+    for (keyword, keyword_aliases) in &document.front_matter.aliases {
+        let (index, _) = keywords.insert_full(UniCase::new(keyword.clone()));
+        for alias in keyword_aliases {
+            let name = UniCase::new(alias.clone());
+            if keywords.contains(&name) || aliases.insert(name, index).is_some() {
+                return Err(format!("Front matter: alias \"{}\" is already used", alias));
+            }
+        }
+    }
+    let (content, warnings) = ParsingState::new(&text, options, &mut keywords, &aliases)
+        .parse_document()
+        .map_err(|e| located(&document, e))?;
+    document.content = content;
+    if !secret_lines.is_empty() {
+        for_each_text_unit_mut(&mut document, &mut |_, _, inlines| {
+            let line = inlines[0].line;
+            let secret = secret_lines.iter().find(|(lines, _)| lines.contains(&line));
+            if let Some((_, audiences)) = secret {
+                mark_secret(inlines, audiences.clone())
+            }
+        })
+    }
+    mark_secret_code(&mut document.content, &secret_lines);
+    let warnings = warnings
+        .into_iter()
+        .map(|w| located(&document, w))
+        .collect();
+    if let Some(audience) = &options.audience {
+        let (document, keywords) = crate::secret::for_audience(document, keywords, audience);
+        return Ok((document, keywords, warnings));
+    }
+    Ok((document, keywords, warnings))
+}
+
+/// Parse a single unnamed text with default options, ignoring warnings.
+#[cfg(test)]
+pub fn parse(text: &str) -> Result<(Document, KeywordSet), String> {
+    let source = Source {
+        name: "<text>".into(),
+        text: text.into(),
+    };
+    let (document, keywords, _) = parse_sources(&[source], &ParseOptions::default())?;
+    Ok((document, keywords))
+}
+
+/******************************************************************************
+ * Front matter.
+ *
+ * Optional metadata header at the very start of the text, delimited by lines:
+ * - "---" for YAML, closed by "---" or "...".
+ * - "+++" for TOML, closed by "+++".
+ * Recognised keys are the fields of FrontMatter, others are ignored for compatibility with other tools.
+ * In YAML, aliases are given as an indented table of lists: "  keyword: [alias, alias]".
+ * In TOML, they are given as an "[aliases]" table.
+ */
+
+/// Parse the front matter if present. Returns it with the offset of the markdown text.
+fn parse_front_matter(text: &str) -> Result<(FrontMatter, usize), Error> {
+    let first_line_end = text.find('\n').map_or(text.len(), |i| i + 1);
+    type MetadataParser = fn(&str) -> Result<metadata::Table, metadata::Error>;
+    let (closing, parser): (&[&str], MetadataParser) = match text[..first_line_end].trim_end() {
+        "---" => (&["---", "..."], metadata::parse_yaml),
+        "+++" => (&["+++"], metadata::parse_toml),
+        _ => return Ok((FrontMatter::default(), 0)),
+    };
+    // Find closing delimiter line
+    let mut line_start = first_line_end;
+    let content_end = loop {
+        if line_start >= text.len() {
+            return Err(("Unclosed front matter".into(), 0));
+        }
+        let line_end = text[line_start..]
+            .find('\n')
+            .map_or(text.len(), |i| line_start + i + 1);
+        if closing.contains(&text[line_start..line_end].trim_end()) {
+            break line_start;
+        }
+        line_start = line_end
+    };
+    let body_offset = text[content_end..]
+        .find('\n')
+        .map_or(text.len(), |i| content_end + i + 1);
+    let content = &text[first_line_end..content_end];
+    let offset_of_line = |n: usize| {
+        let skipped: usize = content.split_inclusive('\n').take(n).map(str::len).sum();
+        first_line_end + skipped
+    };
+    let table = parser(content).map_err(|(msg, n)| (msg, offset_of_line(n)))?;
+    let front_matter = FrontMatter::from_table(&table).map_err(|msg| (msg, first_line_end))?;
+    Ok((front_matter, body_offset))
+}
+
+impl FrontMatter {
+    /// Merge another front matter into this one, keeping already defined single values.
+    fn merge(&mut self, other: FrontMatter) {
+        fn merge_single(value: &mut Option<String>, other: Option<String>) {
+            if value.is_none() {
+                *value = other
+            }
+        }
+        merge_single(&mut self.title, other.title);
+        merge_single(&mut self.author, other.author);
+        merge_single(&mut self.date, other.date);
+        merge_single(&mut self.language, other.language);
+        self.tags.extend(other.tags);
+        self.keywords.extend(other.keywords);
+        self.aliases.extend(other.aliases);
+    }
+
+    fn from_table(table: &metadata::Table) -> Result<FrontMatter, String> {
+        let scalar = |key: &str| -> Result<Option<String>, String> {
+            match metadata::get(table, key) {
+                None => Ok(None),
+                Some(value) => match value.as_scalar() {
+                    Some(s) => Ok(Some(s.to_string())),
+                    None => Err(format!("Front matter: {} must be a string", key)),
+                },
+            }
+        };
+        let list = |key: &str| -> Result<Vec<String>, String> {
+            match metadata::get(table, key) {
+                None => Ok(Vec::new()),
+                Some(value) => value
+                    .as_list()
+                    .ok_or_else(|| format!("Front matter: {} must be a list", key)),
+            }
+        };
+        let aliases = match metadata::get(table, "aliases") {
+            None => Vec::new(),
+            Some(value) => {
+                let entries = value
+                    .as_table()
+                    .ok_or_else(|| "Front matter: aliases must be a table".to_string())?;
+                entries
+                    .iter()
+                    .map(|(keyword, value)| match value.as_list() {
+                        Some(list) => Ok((keyword.clone(), list)),
+                        None => Err(format!(
+                            "Front matter: aliases of {} must be a list",
+                            keyword
+                        )),
+                    })
+                    .collect::<Result<_, String>>()?
+            }
+        };
+        Ok(FrontMatter {
+            title: scalar("title")?,
+            author: scalar("author")?,
+            date: scalar("date")?,
+            tags: list("tags")?,
+            keywords: list("keywords")?,
+            aliases,
+            language: scalar("language")?,
+            secret: match metadata::get(table, "secret") {
+                None => None,
+                Some(value) => match (value.as_scalar(), value.as_list()) {
+                    (Some("false"), _) => None,
+                    (Some("true"), _) => Some(Vec::new()),
+                    (_, Some(audiences)) => Some(audiences),
+                    _ => {
+                        let message = "Front matter: secret must be true, false or a list";
+                        return Err(message.into());
+                    }
+                },
+            },
+        })
     }
 }
 
@@ -396,3 +1124,81 @@ fn parsing() {
     assert_eq!(line_number_of_offset("\nBlah\n", 5), 1);
     assert_eq!(line_number_of_offset("\nBlah\n", 6), 2);
 }
+
+#[test]
+fn sources() {
+    let source = |name: &str, text: &str| Source {
+        name: name.into(),
+        text: text.into(),
+    };
+    let sources = [
+        source("a.md", "---\ntitle: A\ntags: [a]\n---\n# A #\ntext"),
+        source(
+            "b.md",
+            "---\ntitle: B\ntags: [b]\n---\n\nmore *text*\n\n`code`\n",
+        ),
+        source("c.md", "> # Citation #\n> texte\n"),
+    ];
+    let error = parse_sources(&sources, &ParseOptions::default()).unwrap_err();
+    assert_eq!(
+        error,
+        "b.md:8: Parsing paragraph: unexpected Code(Borrowed(\"code\"))"
+    );
+
+    let lenient = ParseOptions {
+        lenient: true,
+        ..ParseOptions::default()
+    };
+    let (document, _, warnings) = parse_sources(&sources, &lenient).unwrap();
+    assert_eq!(
+        warnings,
+        vec![
+            "b.md:8: Inline code kept as text: code",
+            "c.md:1: Skipped unsupported element: Header(1)"
+        ]
+    );
+    assert_eq!(document.front_matter.title.as_deref(), Some("A"));
+    assert_eq!(document.front_matter.tags, vec!["a", "b"]);
+    let section = &document.content.sub_sections[0];
+    assert_eq!(section.content.blocks.len(), 4);
+    match &section.content.blocks[3] {
+        BlockElement::Quote(blocks) => assert_eq!(blocks.len(), 1),
+        _ => panic!("Expected quote"),
+    }
+    let line = match &section.content.blocks[1] {
+        BlockElement::Paragraph(inlines) => inlines[0].line,
+        _ => panic!("Expected paragraph"),
+    };
+    assert_eq!(document.source_location(line), ("b.md", 6));
+}
+
+#[test]
+fn front_matter() {
+    let text = "---\ntitle: Campagne\nkeywords: [Valombre]\naliases:\n  Aldric: [Al]\n---\n# Titre #\n*Al* et *Mira*\n";
+    let (document, keywords) = parse(text).unwrap();
+    let front_matter = &document.front_matter;
+    assert_eq!(front_matter.title.as_deref(), Some("Campagne"));
+    assert_eq!(front_matter.keywords, vec!["Valombre".to_string()]);
+    let keywords: Vec<&str> = keywords.iter().map(|k| k.as_ref()).collect();
+    assert_eq!(keywords, vec!["Valombre", "Aldric", "Mira"]);
+    let line = document.content.sub_sections[0].title.line;
+    assert_eq!(document.source_location(line), ("<text>", 7));
+
+    let (document, _) = parse("+++\ntags = [\"a\"]\n+++\ntext\n").unwrap();
+    assert_eq!(document.front_matter.tags, vec!["a".to_string()]);
+    assert_eq!(
+        parse("---\ntitle: a\n").unwrap_err(),
+        "<text>:1: Unclosed front matter"
+    );
+    assert_eq!(
+        parse("+++\na = 1\nb\n+++\n").unwrap_err(),
+        "<text>:3: Expected \"key = value\""
+    );
+    // Not front matter, but a rule.
+    assert!(parse("text\n\n---\n")
+        .unwrap()
+        .0
+        .front_matter
+        .title
+        .is_none());
+}
@@ -0,0 +1,274 @@
+use crate::ast::{SectionContent, Source};
+use crate::config::Config;
+use crate::document::{for_each_text_unit, IndexedDocument, KeywordIndex};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/******************************************************************************
+ * Build cache for incremental wiki generation.
+ *
+ * Stored in the output directory after each build, as a text file with one entry per line:
+ * - "wimd <version>": cache from another version is ignored.
+ * - "config <hash>": hash of configuration options and theme files affecting the output.
+ * - "index <hash>": hash of keyword and category sets (in index order), of the section outline,
+ *   and of the merged front matter: aliases change implicit occurrences, title and language appear on all pages.
+ * - "source <hash> <keywords> <name>": for each source in order, hash of its text and
+ *   indexes of keywords occurring in it (comma separated, or "-" if none).
+ *
+ * If configuration and source texts are unchanged, the build is skipped.
+ * Otherwise the document is parsed again, as keyword and inline indexes are global to all sources.
+ * If the index hash is unchanged, page names and section anchors are stable,
+ * and only keyword pages of keywords occurring in changed sources (before or after the change) are rendered again.
+ * Otherwise all pages are rendered again.
+ * Hashes use the std hasher and are only valid for one version of wimd.
+ */
+
+pub const CACHE_FILE_NAME: &str = ".wimd-cache";
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct BuildCache {
+    config: u64,
+    index: u64,
+    sources: Vec<SourceEntry>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct SourceEntry {
+    name: String,
+    hash: u64,
+    keywords: BTreeSet<KeywordIndex>,
+}
+
+fn hash<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Hash of configuration options affecting the output, except input and output paths.
+/// Includes files of the theme directory, which are not tracked otherwise.
+fn config_hash(config: &Config) -> u64 {
+    let mut theme_files: Vec<(PathBuf, Option<String>)> = Vec::new();
+    if let Some(Ok(entries)) = config.theme.as_ref().map(fs::read_dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let text = fs::read_to_string(&path).ok();
+            theme_files.push((path, text))
+        }
+        theme_files.sort()
+    }
+    let options = format!(
+        "{:?} {:?} {:?} {:?} {:?} {:?}",
+        config.format, config.parsing, config.matching, theme_files, config.categories, config.code
+    );
+    hash(&options)
+}
+
+fn index_hash(document: &IndexedDocument) -> u64 {
+    fn hash_outline(content: &SectionContent, hasher: &mut DefaultHasher) {
+        content.sub_sections.len().hash(hasher);
+        for section in &content.sub_sections {
+            section.title.string.hash(hasher);
+            hash_outline(&section.content, hasher)
+        }
+    }
+    let mut hasher = DefaultHasher::new();
+    // Exact names, as UniCase hashes are case insensitive
+    for keyword in 0..document.keywords().len() {
+        document.keyword(keyword).hash(&mut hasher);
+        document.keyword_category(keyword).hash(&mut hasher)
+    }
+    for category in 0..document.categories().len() {
+        document.category(category).hash(&mut hasher)
+    }
+    hash_outline(&document.document().content, &mut hasher);
+    format!("{:?}", document.document().front_matter).hash(&mut hasher);
+    hasher.finish()
+}
+
+impl BuildCache {
+    /// Cache describing a build of the document from the given sources.
+    pub fn new(sources: &[Source], config: &Config, document: &IndexedDocument) -> BuildCache {
+        // Source of each inline, from lines of sources in the document
+        let spans = &document.document().sources;
+        let mut inline_sources = vec![0; document.inline_locations().len()];
+        for_each_text_unit(document.document(), &mut |_, _, inlines| {
+            for inline in inlines {
+                let source = spans
+                    .iter()
+                    .rposition(|span| span.first_line <= inline.line)
+                    .unwrap_or(0);
+                inline_sources[inline.index] = source
+            }
+        });
+        let mut keywords = vec![BTreeSet::new(); sources.len()];
+        for keyword in 0..document.keywords().len() {
+            let explicit = document.explicit_occurrences(keyword).iter();
+            let implicit = document.implicit_occurrences(keyword).iter();
+            for inline in explicit.chain(implicit) {
+                keywords[inline_sources[*inline]].insert(keyword);
+            }
+        }
+        BuildCache {
+            config: config_hash(config),
+            index: index_hash(document),
+            sources: sources
+                .iter()
+                .zip(keywords)
+                .map(|(source, keywords)| SourceEntry {
+                    name: source.name.clone(),
+                    hash: hash(&source.text),
+                    keywords,
+                })
+                .collect(),
+        }
+    }
+
+    /// True if a build from these sources would produce the same output.
+    pub fn is_up_to_date(&self, sources: &[Source], config: &Config) -> bool {
+        self.config == config_hash(config)
+            && self.sources.len() == sources.len()
+            && self.sources.iter().zip(sources).all(|(entry, source)| {
+                entry.name == source.name && entry.hash == hash(&source.text)
+            })
+    }
+
+    /// Keywords whose pages must be rendered again, from this previous build to the new one.
+    /// None if all pages must be rendered again.
+    pub fn changed_keywords(&self, new: &BuildCache) -> Option<BTreeSet<KeywordIndex>> {
+        let same_sources = self.sources.len() == new.sources.len()
+            && self
+                .sources
+                .iter()
+                .zip(&new.sources)
+                .all(|(old, new)| old.name == new.name);
+        if self.config != new.config || self.index != new.index || !same_sources {
+            return None;
+        }
+        let mut changed = BTreeSet::new();
+        for (old, new) in self.sources.iter().zip(&new.sources) {
+            if old.hash != new.hash {
+                changed.extend(&old.keywords);
+                changed.extend(&new.keywords);
+            }
+        }
+        Some(changed)
+    }
+
+    /// Load the cache from a file. None if missing, invalid, or from another version.
+    pub fn load(path: &Path) -> Option<BuildCache> {
+        let text = fs::read_to_string(path).ok()?;
+        let mut lines = text.lines();
+        if lines.next()? != format!("wimd {}", env!("CARGO_PKG_VERSION")) {
+            return None;
+        }
+        let mut value = |key: &str| {
+            let hex = lines.next()?.strip_prefix(key)?.strip_prefix(' ')?;
+            u64::from_str_radix(hex, 16).ok()
+        };
+        let config = value("config")?;
+        let index = value("index")?;
+        let sources = lines
+            .map(|line| {
+                let mut fields = line.strip_prefix("source ")?.splitn(3, ' ');
+                let hash = u64::from_str_radix(fields.next()?, 16).ok()?;
+                let keywords = match fields.next()? {
+                    "-" => BTreeSet::new(),
+                    list => list
+                        .split(',')
+                        .map(|k| k.parse().ok())
+                        .collect::<Option<_>>()?,
+                };
+                let name = fields.next()?.to_string();
+                Some(SourceEntry {
+                    name,
+                    hash,
+                    keywords,
+                })
+            })
+            .collect::<Option<_>>()?;
+        Some(BuildCache {
+            config,
+            index,
+            sources,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut text = String::new();
+        writeln!(text, "wimd {}", env!("CARGO_PKG_VERSION")).unwrap();
+        writeln!(text, "config {:016x}", self.config).unwrap();
+        writeln!(text, "index {:016x}", self.index).unwrap();
+        for entry in &self.sources {
+            let keywords: Vec<String> = entry.keywords.iter().map(|k| k.to_string()).collect();
+            let keywords = if keywords.is_empty() {
+                "-".to_string()
+            } else {
+                keywords.join(",")
+            };
+            writeln!(
+                text,
+                "source {:016x} {} {}",
+                entry.hash, keywords, entry.name
+            )
+            .unwrap();
+        }
+        fs::write(path, text)
+    }
+}
+
+#[test]
+fn incremental() {
+    use crate::ast::{parse_sources, ParseOptions};
+    let config = Config::default_in(PathBuf::from("."));
+    let build = |texts: &[&str]| {
+        let sources: Vec<Source> = texts
+            .iter()
+            .enumerate()
+            .map(|(i, text)| Source {
+                name: format!("{}.md", i),
+                text: text.to_string(),
+            })
+            .collect();
+        let (document, keywords, _) = parse_sources(&sources, &ParseOptions::default()).unwrap();
+        let document = IndexedDocument::from(document, keywords);
+        (BuildCache::new(&sources, &config, &document), sources)
+    };
+    let (old, sources) = build(&["# Un #\n*a* *b*\n", "# Deux #\n*c* and a\n"]);
+    assert!(old.is_up_to_date(&sources, &config));
+    assert_eq!(old.sources[1].keywords, vec![0, 2].into_iter().collect());
+
+    // Same keywords and outline: only keywords of the changed source
+    let (new, sources) = build(&["# Un #\n*a* *b*\n", "# Deux #\n*c* alone\n"]);
+    assert!(!old.is_up_to_date(&sources, &config));
+    let changed = old.changed_keywords(&new).unwrap();
+    assert_eq!(changed, vec![0, 2].into_iter().collect());
+
+    // New keyword or section: everything
+    let (new, _) = build(&["# Un #\n*a* *b*\n", "# Deux #\n*c* *d*\n"]);
+    assert_eq!(old.changed_keywords(&new), None);
+    let (new, _) = build(&["# Un #\n*a* *b*\n", "# Deux #\n*c*\n# Trois #\n"]);
+    assert_eq!(old.changed_keywords(&new), None);
+
+    // Front matter change in one source: everything
+    let (new, _) = build(&[
+        "---\naliases:\n  c: [alone]\n---\n# Un #\n*a* *b*\n",
+        "# Deux #\n*c* and a\n",
+    ]);
+    assert_eq!(old.changed_keywords(&new), None);
+    let (new, _) = build(&[
+        "---\nlanguage: fr\n---\n# Un #\n*a* *b*\n",
+        "# Deux #\n*c* and a\n",
+    ]);
+    assert_eq!(old.changed_keywords(&new), None);
+
+    let path = std::env::temp_dir().join(format!("wimd-cache-test-{}", std::process::id()));
+    old.save(&path).unwrap();
+    assert_eq!(BuildCache::load(&path), Some(old));
+    fs::remove_file(&path).unwrap();
+}
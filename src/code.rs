@@ -0,0 +1,206 @@
+use crate::ast::*;
+use crate::document::KeywordIndex;
+
+/******************************************************************************
+ * Interpretation of code blocks as structured data.
+ *
+ * Code blocks are kept verbatim in the document. Interpreters registered for the language of a fenced
+ * block ("```statblock") additionally extract a list of named fields from its text.
+ * Interpreters are given to the document index, from the configuration by default, see the config module.
+ * The data is attached to the surrounding keyword:
+ * - the explicit keyword starting the paragraph just before the block: "*Goblin* :" then the block.
+ * - otherwise the first explicit keyword of the enclosing section title.
+ * Blocks without a surrounding keyword, or without an interpreter for their language, are only text.
+ */
+
+/// Interpreter of the code blocks of a language.
+pub trait CodeInterpreter {
+    /// Language handled, compared to the first word of the info string.
+    fn lang(&self) -> &str;
+    /// Named fields extracted from the block text.
+    fn interpret(&self, text: &str) -> Vec<(String, String)>;
+}
+
+/// Interpreter kinds by language used by default: fields of "statblock" blocks.
+pub const DEFAULT_INTERPRETERS: &[(&str, &str)] = &[("statblock", "fields")];
+
+/// Interpreters used by default.
+#[cfg(test)]
+pub fn interpreters() -> Vec<Box<dyn CodeInterpreter>> {
+    (DEFAULT_INTERPRETERS.iter())
+        .filter_map(|(lang, kind)| interpreter(kind, lang))
+        .collect()
+}
+
+/// Interpreter of code blocks of a language by kind name, or None if the kind is unknown.
+pub fn interpreter(kind: &str, lang: &str) -> Option<Box<dyn CodeInterpreter>> {
+    let lang = lang.to_string();
+    match kind {
+        "fields" => Some(Box::new(Fields { lang })),
+        _ => None,
+    }
+}
+
+/// Named fields, like the stat block of a creature: one "key: value" field by line.
+/// Lines without a colon continue the value of the previous field.
+pub struct Fields {
+    pub lang: String,
+}
+
+impl CodeInterpreter for Fields {
+    fn lang(&self) -> &str {
+        &self.lang
+    }
+    fn interpret(&self, text: &str) -> Vec<(String, String)> {
+        let mut fields: Vec<(String, String)> = Vec::new();
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            match (line.split_once(':'), fields.last_mut()) {
+                (Some((key, value)), _) if !key.trim().is_empty() => {
+                    fields.push((key.trim().to_string(), value.trim().to_string()))
+                }
+                (_, Some((_, value))) => {
+                    if !value.is_empty() {
+                        value.push(' ')
+                    }
+                    value.push_str(line)
+                }
+                (_, None) => fields.push((String::new(), line.to_string())),
+            }
+        }
+        fields
+    }
+}
+
+/// Data extracted from a code block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeData {
+    pub lang: String,
+    pub fields: Vec<(String, String)>,
+}
+
+/// Data of interpreted code blocks attached to each keyword, in document order.
+pub fn keyword_data(
+    document: &Document,
+    keyword_count: usize,
+    interpreters: &[Box<dyn CodeInterpreter>],
+) -> Vec<Vec<CodeData>> {
+    let mut data = vec![Vec::new(); keyword_count];
+    let mut add = |keyword: Option<KeywordIndex>, lang: &Option<String>, text: &str| {
+        let lang = match lang {
+            Some(lang) => lang,
+            None => return,
+        };
+        let interpreter = interpreters.iter().find(|i| i.lang() == lang);
+        if let (Some(keyword), Some(interpreter)) = (keyword, interpreter) {
+            data[keyword].push(CodeData {
+                lang: lang.clone(),
+                fields: interpreter.interpret(text),
+            })
+        }
+    };
+    content_data(&document.content, None, &mut add);
+    data
+}
+
+type AddData<'a> = dyn FnMut(Option<KeywordIndex>, &Option<String>, &str) + 'a;
+
+fn content_data(
+    content: &SectionContent,
+    section_keyword: Option<KeywordIndex>,
+    add: &mut AddData,
+) {
+    blocks_data(&content.blocks, section_keyword, add);
+    for section in &content.sub_sections {
+        let keyword = section.title.tags.iter().find_map(|(_, tag)| match tag {
+            InlineTag::ExplicitKeyword(k) => Some(*k),
+            _ => None,
+        });
+        content_data(&section.content, keyword, add)
+    }
+}
+
+fn blocks_data(blocks: &[BlockElement], section_keyword: Option<KeywordIndex>, add: &mut AddData) {
+    for (i, block) in blocks.iter().enumerate() {
+        match block {
+            BlockElement::Code { lang, text, .. } => {
+                let previous = match i.checked_sub(1).map(|i| &blocks[i]) {
+                    Some(BlockElement::Paragraph(inlines)) => leading_keyword(inlines),
+                    _ => None,
+                };
+                add(previous.or(section_keyword), lang, text)
+            }
+            BlockElement::Quote(blocks) => blocks_data(blocks, section_keyword, add),
+            BlockElement::Paragraph(_)
+            | BlockElement::List(_)
+            | BlockElement::Rule
+            | BlockElement::Table(_) => (),
+        }
+    }
+}
+
+/// Explicit keyword at the start of a text unit.
+fn leading_keyword(inlines: &[InlineElement]) -> Option<KeywordIndex> {
+    let first = inlines.first()?;
+    let leading = first.string.len() - first.string.trim_start().len();
+    first.tags.iter().find_map(|(range, tag)| match tag {
+        InlineTag::ExplicitKeyword(k) if range.start == leading => Some(*k),
+        _ => None,
+    })
+}
+
+#[test]
+fn stat_blocks() {
+    let text = "\
+# Bestiaire #
+*Gobelin* :
+
+```statblock
+PV: 7
+Attaque: dague,
+  1d4 dégâts
+```
+
+## *Troll* ##
+Grand.
+
+```statblock
+PV: 84
+```
+
+```
+PV: 1
+```
+";
+    let (document, keywords) = parse(text).unwrap();
+    let data = keyword_data(&document, keywords.len(), &interpreters());
+    let field = |k: &str, v: &str| (k.to_string(), v.to_string());
+    let expected = vec![
+        vec![CodeData {
+            lang: "statblock".into(),
+            fields: vec![field("PV", "7"), field("Attaque", "dague, 1d4 dégâts")],
+        }],
+        vec![CodeData {
+            lang: "statblock".into(),
+            fields: vec![field("PV", "84")],
+        }],
+    ];
+    assert_eq!(data, expected);
+
+    // Interpreters are given by the caller
+    struct Lines;
+    impl CodeInterpreter for Lines {
+        fn lang(&self) -> &str {
+            "statblock"
+        }
+        fn interpret(&self, text: &str) -> Vec<(String, String)> {
+            text.lines()
+                .map(|l| (String::new(), l.to_string()))
+                .collect()
+        }
+    }
+    let data = keyword_data(&document, keywords.len(), &[Box::new(Lines)]);
+    assert_eq!(data[1][0].fields, vec![field("", "PV: 84")]);
+    assert!(keyword_data(&document, keywords.len(), &[])
+        .iter()
+        .all(Vec::is_empty));
+}
@@ -0,0 +1,302 @@
+use crate::ast::ParseOptions;
+use crate::code::{self, CodeInterpreter};
+use crate::document::MatchingOptions;
+use crate::metadata::{self, Table, Value};
+use crate::secret;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use unicase::UniCase;
+
+/******************************************************************************
+ * Project configuration.
+ *
+ * Read from a "wimd.toml" file, searched in the working directory and then its parents.
+ * Relative paths are resolved from the directory of the configuration file.
+ * All keys are optional, defaults are shown:
+ *
+ * inputs = []          # Glob patterns of input files, concatenated in order. Standard input if empty.
+ * output = "wiki"      # Output directory of the wiki.
+ * format = "html"      # Format of the wiki: "html" or "markdown".
+ * theme = "<dir>"      # Directory with templates and style.css replacing those of the built-in wiki theme.
+ * lenient = false      # Skip unsupported markdown elements with a warning, instead of failing.
+ * strip_secrets = false # Remove secret content, and keywords only occurring in it.
+ * audiences = []       # Build one wiki per audience, in <output>/<audience slug>, with the content visible to it.
+ *                      # Other commands use the most restricted audience, see the secret module.
+ *
+ * [matching]           # Search of implicit keyword occurrences.
+ * case_folding = true
+ * accent_folding = false
+ * stop_list = []       # Keywords or aliases never searched.
+ * min_length = 0       # Minimum number of characters for searched keywords or aliases.
+ *
+ * [code]               # Interpretation of fenced code blocks as data, by language, see the code module.
+ * statblock = "fields" # "fields": one "key: value" field by line. "none": only text.
+ *
+ * [categories.<name>]  # Per category settings, by case insensitive category name.
+ * colour = "#884400"   # Html colour of keyword links, instead of a generated one.
+ *
+ * Command line flags override values from the configuration file.
+ */
+
+pub const CONFIG_FILE_NAME: &str = "wimd.toml";
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Directory used to resolve relative paths.
+    pub root: PathBuf,
+    pub inputs: Vec<String>,
+    pub output: PathBuf,
+    pub format: WikiFormat,
+    pub theme: Option<PathBuf>,
+    pub parsing: ParseOptions,
+    pub matching: MatchingOptions,
+    pub categories: Vec<(UniCase<String>, CategorySettings)>,
+    /// Audiences of the wiki builds, each in its own sub directory. A single build if empty.
+    pub audiences: Vec<String>,
+    /// Interpreter kind of code blocks, by language.
+    pub code: Vec<(String, String)>,
+}
+
+/// Format of the generated wiki.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WikiFormat {
+    Html,
+    Markdown,
+}
+
+impl WikiFormat {
+    pub fn from_name(name: &str) -> Option<WikiFormat> {
+        match name {
+            "html" => Some(WikiFormat::Html),
+            "markdown" => Some(WikiFormat::Markdown),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct CategorySettings {
+    pub colour: Option<String>,
+}
+
+impl Config {
+    /// Default configuration, with paths relative to root.
+    pub fn default_in(root: PathBuf) -> Config {
+        Config {
+            output: root.join("wiki"),
+            root,
+            inputs: Vec::new(),
+            format: WikiFormat::Html,
+            theme: None,
+            parsing: ParseOptions::default(),
+            matching: MatchingOptions::default(),
+            categories: Vec::new(),
+            audiences: Vec::new(),
+            code: (code::DEFAULT_INTERPRETERS.iter())
+                .map(|(lang, kind)| (lang.to_string(), kind.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Search the configuration file from the working directory.
+    pub fn find() -> Option<PathBuf> {
+        let working_dir = env::current_dir().ok()?;
+        working_dir
+            .ancestors()
+            .map(|dir| dir.join(CONFIG_FILE_NAME))
+            .find(|path| path.is_file())
+    }
+
+    /// Search the configuration file from the working directory, or use defaults if not found.
+    pub fn discover() -> Result<Config, String> {
+        match Config::find() {
+            Some(path) => Config::load(&path),
+            None => Ok(Config::default_in(
+                env::current_dir().map_err(|e| e.to_string())?,
+            )),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Config, String> {
+        let error = |msg: String| format!("{}: {}", path.display(), msg);
+        let text = fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        let table = metadata::parse_toml(&text)
+            .map_err(|(msg, n)| format!("{}:{}: {}", path.display(), n + 1, msg))?;
+        let root = match path.parent() {
+            Some(dir) if dir != Path::new("") => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let mut config = Config::default_in(root);
+        config.read_table(&table).map_err(error)?;
+        Ok(config)
+    }
+
+    fn read_table(&mut self, table: &Table) -> Result<(), String> {
+        for (key, value) in table {
+            match key.as_str() {
+                "inputs" => self.inputs = list(key, value)?,
+                "output" => self.output = self.root.join(scalar(key, value)?),
+                "format" => {
+                    let name = scalar(key, value)?;
+                    self.format = WikiFormat::from_name(&name).ok_or_else(|| {
+                        format!("Invalid format {}, expected html or markdown", name)
+                    })?
+                }
+                "theme" => self.theme = Some(self.root.join(scalar(key, value)?)),
+                "lenient" => self.parsing.lenient = boolean(key, value)?,
+                "strip_secrets" => {
+                    let strip = boolean(key, value)?;
+                    self.parsing.audience = Some(secret::PUBLIC.to_string()).filter(|_| strip)
+                }
+                "audiences" => self.audiences = list(key, value)?,
+                "matching" => {
+                    for (key, value) in sub_table(key, value)? {
+                        let matching = &mut self.matching;
+                        match key.as_str() {
+                            "case_folding" => matching.case_folding = boolean(key, value)?,
+                            "accent_folding" => matching.accent_folding = boolean(key, value)?,
+                            "stop_list" => matching.stop_list = list(key, value)?,
+                            "min_length" => matching.min_length = integer(key, value)?,
+                            _ => return Err(format!("Unknown key matching.{}", key)),
+                        }
+                    }
+                }
+                "code" => {
+                    for (lang, value) in sub_table(key, value)? {
+                        let kind = scalar(lang, value)?;
+                        if kind != "none" && code::interpreter(&kind, lang).is_none() {
+                            return Err(format!(
+                                "Invalid code.{} {}, expected fields or none",
+                                lang, kind
+                            ));
+                        }
+                        self.code.retain(|(l, _)| l != lang);
+                        self.code.push((lang.clone(), kind))
+                    }
+                }
+                "categories" => {
+                    for (name, value) in sub_table(key, value)? {
+                        let mut settings = CategorySettings::default();
+                        for (key, value) in sub_table(name, value)? {
+                            match key.as_str() {
+                                "colour" => settings.colour = Some(scalar(key, value)?),
+                                _ => {
+                                    return Err(format!("Unknown key categories.{}.{}", name, key))
+                                }
+                            }
+                        }
+                        self.categories.push((UniCase::new(name.clone()), settings))
+                    }
+                }
+                _ => return Err(format!("Unknown key {}", key)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Interpreters of code blocks.
+    pub fn code_interpreters(&self) -> Vec<Box<dyn CodeInterpreter>> {
+        (self.code.iter())
+            .filter_map(|(lang, kind)| code::interpreter(kind, lang))
+            .collect()
+    }
+
+    /// Settings of a category, by case insensitive name.
+    pub fn category_settings(&self, name: &str) -> Option<&CategorySettings> {
+        let name = UniCase::new(name);
+        self.categories
+            .iter()
+            .find(|(n, _)| UniCase::new(n.as_ref()) == name)
+            .map(|(_, settings)| settings)
+    }
+
+    /// Input files matching the input patterns, in order of patterns then alphabetical order.
+    /// Files matched by multiple patterns are only included once.
+    pub fn input_files(&self) -> Result<Vec<PathBuf>, String> {
+        let mut files: Vec<PathBuf> = Vec::new();
+        for pattern in &self.inputs {
+            let full_pattern = self.root.join(pattern);
+            let full_pattern = full_pattern.to_string_lossy();
+            let paths = glob::glob(&full_pattern)
+                .map_err(|e| format!("Input pattern {}: {}", pattern, e))?;
+            let mut matched = false;
+            for path in paths {
+                let path = path.map_err(|e| e.to_string())?;
+                matched = true;
+                if !files.contains(&path) {
+                    files.push(path)
+                }
+            }
+            if !matched {
+                return Err(format!("Input pattern {} matches no file", pattern));
+            }
+        }
+        Ok(files)
+    }
+}
+
+fn scalar(key: &str, value: &Value) -> Result<String, String> {
+    match value.as_scalar() {
+        Some(s) => Ok(s.to_string()),
+        None => Err(format!("{} must be a string", key)),
+    }
+}
+fn list(key: &str, value: &Value) -> Result<Vec<String>, String> {
+    value
+        .as_list()
+        .ok_or_else(|| format!("{} must be a list", key))
+}
+fn sub_table<'v>(key: &str, value: &'v Value) -> Result<&'v Table, String> {
+    value
+        .as_table()
+        .ok_or_else(|| format!("{} must be a table", key))
+}
+fn boolean(key: &str, value: &Value) -> Result<bool, String> {
+    match value.as_scalar() {
+        Some("true") => Ok(true),
+        Some("false") => Ok(false),
+        _ => Err(format!("{} must be true or false", key)),
+    }
+}
+fn integer(key: &str, value: &Value) -> Result<usize, String> {
+    let parsed = value.as_scalar().map(str::parse);
+    match parsed {
+        Some(Ok(n)) => Ok(n),
+        _ => Err(format!("{} must be a positive integer", key)),
+    }
+}
+
+#[test]
+fn configuration() {
+    let text = "inputs = [\"*.md\"]\noutput = \"out\"\nformat = \"markdown\"\n[matching]\nmin_length = 3\n[categories.Lieux]\ncolour = \"red\"\n";
+    let table = metadata::parse_toml(text).unwrap();
+    let mut config = Config::default_in(PathBuf::from("root"));
+    config.read_table(&table).unwrap();
+    assert_eq!(config.inputs, vec!["*.md"]);
+    assert_eq!(config.output, PathBuf::from("root/out"));
+    assert_eq!(config.format, WikiFormat::Markdown);
+    assert_eq!(config.matching.min_length, 3);
+    assert!(config.matching.case_folding);
+    let settings = config.category_settings("lieux").unwrap();
+    assert_eq!(settings.colour.as_deref(), Some("red"));
+
+    let mut config = Config::default_in(PathBuf::from("root"));
+    let table = metadata::parse_toml("[matching]\nfoo = 1\n").unwrap();
+    assert_eq!(
+        config.read_table(&table).unwrap_err(),
+        "Unknown key matching.foo"
+    );
+
+    let table = metadata::parse_toml("[code]\nstatblock = \"none\"\npnj = \"fields\"\n").unwrap();
+    config.read_table(&table).unwrap();
+    let languages: Vec<String> = (config.code_interpreters().iter())
+        .map(|i| i.lang().to_string())
+        .collect();
+    assert_eq!(languages, vec!["pnj"]);
+    let table = metadata::parse_toml("[code]\npnj = \"yaml\"\n").unwrap();
+    assert_eq!(
+        config.read_table(&table).unwrap_err(),
+        "Invalid code.pnj yaml, expected fields or none"
+    );
+}
@@ -1,79 +1,652 @@
 use crate::ast::*;
+use crate::code::{CodeData, CodeInterpreter};
+use indexmap::IndexSet;
 use regex::{escape as escape_regex_special_chars, Regex, RegexBuilder};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt::{Display, Write};
+use std::slice;
+use unicase::UniCase;
 
-type KeywordIndex = usize;
+/// Keywords are referenced by their index in the KeywordSet.
+pub type KeywordIndex = usize;
 
+/// Categories are referenced by their index in the CategorySet.
+pub type CategoryIndex = usize;
+
+/// Set of keyword categories: indexed, and case insensitive.
+pub type CategorySet = IndexSet<UniCase<String>>;
+
+/// Document with keyword occurrence tables.
+/// Occurrence tables are indexed by keyword index, and list inline indexes in increasing order.
+/// Implicit occurrences of keyword aliases are recorded for the keyword.
+///
+/// Keywords may have a category, assigned by their first definition.
+/// A definition is a paragraph or list item starting with an explicit keyword followed by a colon.
+/// The category is given by a suffix "*kwd* (category): text", or else is the enclosing section title.
+/// Definitions outside of any section without suffix do not assign a category.
 pub struct IndexedDocument {
     root: Document,
     keywords: KeywordSet,
+    /// Alternative names of keywords, from front matter.
+    aliases: HashMap<UniCase<String>, KeywordIndex>,
+    categories: CategorySet,
+    keyword_categories: Vec<Option<CategoryIndex>>,
     explicit_keyword_occurrences: Vec<Vec<InlineIndex>>,
     implicit_keyword_occurrences: Vec<Vec<InlineIndex>>,
+    inline_locations: Vec<InlineLocation>,
+    /// Anchor slug of each section by index path, unique in the document.
+    section_slugs: HashMap<Vec<usize>, String>,
+    /// Slugs of keyword and category names, unique among keywords and among categories.
+    keyword_slugs: Vec<String>,
+    category_slugs: Vec<String>,
+    /// Data of interpreted code blocks attached to each keyword, see the code module.
+    keyword_data: Vec<Vec<CodeData>>,
 }
 
-impl IndexedDocument {
-    pub fn from(mut document: Document, keywords: KeywordSet) -> IndexedDocument {
-        let regex = keyword_search_regex(&keywords).unwrap();
-        let scan_inline = |inline: &mut InlineElement| {
-            //
+/// Position of an inline element in the document structure.
+#[derive(Debug, Clone)]
+pub struct InlineLocation {
+    /// Index path of the enclosing section, as used by `Section::section`. Empty for root blocks.
+    pub section: Vec<usize>,
+    /// Index of the enclosing text unit, by order of appearance in the document.
+    pub unit: usize,
+}
+
+/// Kind of a text unit: the smallest structural element containing text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextUnitKind {
+    /// Section title, with header level.
+    Title(usize),
+    Paragraph,
+    /// List item, with ordering of the list and list depth (starting at 0).
+    ListItem {
+        ordered: bool,
+        depth: usize,
+    },
+    /// Non empty table cell, from the header row or not.
+    TableCell {
+        header: bool,
+    },
+}
+
+/// Options for the search of implicit keyword occurrences.
+#[derive(Debug, Clone)]
+pub struct MatchingOptions {
+    /// Ignore case differences, enabled by default.
+    pub case_folding: bool,
+    /// Ignore accents of latin letters: "general" matches "Général".
+    pub accent_folding: bool,
+    /// Names (keywords or aliases) never searched, like common words.
+    pub stop_list: Vec<String>,
+    /// Names shorter than this number of characters are not searched.
+    pub min_length: usize,
+}
+
+impl Default for MatchingOptions {
+    fn default() -> Self {
+        MatchingOptions {
+            case_folding: true,
+            accent_folding: false,
+            stop_list: Vec::new(),
+            min_length: 0,
+        }
+    }
+}
+
+impl MatchingOptions {
+    /// Normalized form of a name, equal for names matching each other.
+    fn normalize(&self, name: &str) -> String {
+        let name = match self.accent_folding {
+            true => fold_accents(name).0,
+            false => name.to_string(),
         };
-        let scan_blocks = |blocks: &mut [BlockElement]| {
-            for block in blocks {
-                match block {
-                    BlockElement::Paragraph(inlines) => inlines.into_iter().for_each(scan_inline),
-                    BlockElement::Rule => (),
-                    BlockElement::List(_) => unimplemented!(),
+        match self.case_folding {
+            true => name.to_lowercase(),
+            false => name,
+        }
+    }
+}
+
+impl IndexedDocument {
+    /// Index with default matching options.
+    #[cfg(test)]
+    pub fn from(document: Document, keywords: KeywordSet) -> IndexedDocument {
+        let interpreters = crate::code::interpreters();
+        Self::with_options(
+            document,
+            keywords,
+            &MatchingOptions::default(),
+            &interpreters,
+        )
+    }
+
+    pub fn with_options(
+        mut document: Document,
+        keywords: KeywordSet,
+        options: &MatchingOptions,
+        interpreters: &[Box<dyn CodeInterpreter>],
+    ) -> IndexedDocument {
+        let mut explicit_keyword_occurrences = vec![Vec::new(); keywords.len()];
+        let mut implicit_keyword_occurrences = vec![Vec::new(); keywords.len()];
+        let mut inline_locations = Vec::new();
+
+        let mut aliases = HashMap::new();
+        for (keyword, keyword_aliases) in &document.front_matter.aliases {
+            let (index, _) = keywords
+                .get_full(&UniCase::new(keyword.clone()))
+                .expect("Alias targets are in the keyword set");
+            for alias in keyword_aliases {
+                aliases.insert(UniCase::new(alias.clone()), index);
+            }
+        }
+
+        // Normalized names searched implicitly. Keywords have priority over aliases.
+        let stop_list: HashSet<String> = options
+            .stop_list
+            .iter()
+            .map(|name| options.normalize(name))
+            .collect();
+        let mut search_names: HashMap<String, KeywordIndex> = HashMap::new();
+        let names = keywords.iter().enumerate();
+        let alias_names = aliases.iter().map(|(alias, index)| (*index, alias));
+        for (index, name) in names.chain(alias_names) {
+            search_names.entry(options.normalize(name)).or_insert(index);
+        }
+        search_names.retain(|name, _| {
+            name.chars().count() >= options.min_length && !stop_list.contains(name)
+        });
+
+        let regex = keyword_search_regex(
+            search_names.keys().map(String::as_str),
+            options.case_folding,
+        );
+        let mut unit = 0;
+        for_each_text_unit_mut(&mut document, &mut |section, _, inlines| {
+            for inline in inlines {
+                if let Some(regex) = &regex {
+                    add_implicit_keyword_tags(inline, regex, options.accent_folding, |name| {
+                        search_names.get(&options.normalize(name)).cloned()
+                    })
+                }
+                for (_, tag) in &inline.tags {
+                    let (table, keyword) = match tag {
+                        InlineTag::ExplicitKeyword(k) => (&mut explicit_keyword_occurrences, *k),
+                        InlineTag::ImplicitKeyword(k) => (&mut implicit_keyword_occurrences, *k),
+                        InlineTag::Highlight | InlineTag::Secret(_) => continue,
+                    };
+                    let occurrences: &mut Vec<InlineIndex> = &mut table[keyword];
+                    if occurrences.last() != Some(&inline.index) {
+                        occurrences.push(inline.index)
+                    }
                 }
+                assert_eq!(inline.index, inline_locations.len());
+                inline_locations.push(InlineLocation {
+                    section: section.to_vec(),
+                    unit,
+                })
             }
-        };
-        scan_blocks(&mut document.blocks);
-        for section in &mut document.sub_sections {
-            section_dfs_mut(section, &mut |s: &mut Section| {
-                scan_inline(&mut s.title);
-                scan_blocks(&mut s.content.blocks)
+            unit += 1
+        });
+
+        let mut categories = CategorySet::new();
+        let mut keyword_categories = vec![None; keywords.len()];
+        let definition_regex = definition_regex();
+        for_each_text_unit(&document, &mut |section, kind, inlines| {
+            if let TextUnitKind::Title(_) = kind {
+                return;
+            }
+            let (keyword, suffix, _) = match definition(&inlines[0], &definition_regex) {
+                Some(definition) => definition,
+                None => return,
+            };
+            let category = match suffix {
+                Some(category) => category,
+                None => match document.content.section(section.iter().cloned()) {
+                    Some(section) => section.title.string.trim(),
+                    None => return,
+                },
+            };
+            if keyword_categories[keyword].is_none() {
+                let (index, _) = categories.insert_full(UniCase::new(category.to_string()));
+                keyword_categories[keyword] = Some(index)
+            }
+        });
+
+        let section_slugs = section_slugs(&document.content);
+        let keyword_slugs = unique_slugs(keywords.iter().map(|k| k.as_ref()), "keyword");
+        let category_slugs = unique_slugs(categories.iter().map(|c| c.as_ref()), "category");
+        let keyword_data = crate::code::keyword_data(&document, keywords.len(), interpreters);
+
+        IndexedDocument {
+            root: document,
+            keywords,
+            aliases,
+            categories,
+            keyword_categories,
+            explicit_keyword_occurrences,
+            implicit_keyword_occurrences,
+            inline_locations,
+            section_slugs,
+            keyword_slugs,
+            category_slugs,
+            keyword_data,
+        }
+    }
+
+    pub fn document(&self) -> &Document {
+        &self.root
+    }
+    pub fn keywords(&self) -> &KeywordSet {
+        &self.keywords
+    }
+    pub fn keyword(&self, keyword: KeywordIndex) -> &str {
+        self.keywords.get_index(keyword).unwrap().as_ref()
+    }
+    pub fn categories(&self) -> &CategorySet {
+        &self.categories
+    }
+    pub fn category(&self, category: CategoryIndex) -> &str {
+        self.categories.get_index(category).unwrap().as_ref()
+    }
+    pub fn keyword_category(&self, keyword: KeywordIndex) -> Option<CategoryIndex> {
+        self.keyword_categories[keyword]
+    }
+    /// Slug of the keyword name, stable while keyword names are.
+    pub fn keyword_slug(&self, keyword: KeywordIndex) -> &str {
+        &self.keyword_slugs[keyword]
+    }
+    pub fn category_slug(&self, category: CategoryIndex) -> &str {
+        &self.category_slugs[category]
+    }
+    pub fn keyword_data(&self, keyword: KeywordIndex) -> &[CodeData] {
+        &self.keyword_data[keyword]
+    }
+    /// Keywords of a category, in keyword index order.
+    pub fn category_keywords(&self, category: CategoryIndex) -> Vec<KeywordIndex> {
+        (0..self.keywords.len())
+            .filter(|k| self.keyword_categories[*k] == Some(category))
+            .collect()
+    }
+    /// Find a keyword index from its name or an alias, case insensitive.
+    pub fn keyword_index(&self, keyword: &str) -> Option<KeywordIndex> {
+        find_keyword(&self.keywords, &self.aliases, keyword)
+    }
+    /// Inlines where the keyword is tagged with emphasis.
+    pub fn explicit_occurrences(&self, keyword: KeywordIndex) -> &[InlineIndex] {
+        &self.explicit_keyword_occurrences[keyword]
+    }
+    /// Inlines where the keyword was found by search, without emphasis.
+    pub fn implicit_occurrences(&self, keyword: KeywordIndex) -> &[InlineIndex] {
+        &self.implicit_keyword_occurrences[keyword]
+    }
+    pub fn inline_location(&self, inline: InlineIndex) -> &InlineLocation {
+        &self.inline_locations[inline]
+    }
+    pub fn inline_locations(&self) -> &[InlineLocation] {
+        &self.inline_locations
+    }
+    /// Index paths of sections directly containing occurrences of the keyword, in document order.
+    pub fn referencing_sections(&self, keyword: KeywordIndex) -> BTreeSet<Vec<usize>> {
+        let explicit = self.explicit_occurrences(keyword).iter();
+        let implicit = self.implicit_occurrences(keyword).iter();
+        explicit
+            .chain(implicit)
+            .map(|inline| self.inline_location(*inline).section.clone())
+            .collect()
+    }
+    /// Titles of the sections along an index path, from outermost to innermost.
+    pub fn section_breadcrumb(&self, path: &[usize]) -> Vec<&str> {
+        (1..=path.len())
+            .map(|depth| {
+                let section = self.root.content.section(path[..depth].iter().cloned());
+                section.expect("Invalid section path").title.string.as_str()
             })
+            .collect()
+    }
+    /// Anchor slug of a section, stable as long as titles do not change. None for root.
+    pub fn section_slug(&self, path: &[usize]) -> Option<&str> {
+        self.section_slugs.get(path).map(String::as_str)
+    }
+    /// Section breadcrumb as displayed to users: titles separated by " > ", "(top)" for root.
+    pub fn section_display_name(&self, path: &[usize]) -> String {
+        if path.is_empty() {
+            "(top)".to_string()
+        } else {
+            self.section_breadcrumb(path).join(" > ")
         }
+    }
+}
 
-        let matches: Vec<&str> = regex
-            .find_iter("wimd a wimdaa hello Wimd")
-            .map(|m| m.as_str())
-            .collect();
-        println!("MATCHES: {:?}", matches);
-        unimplemented!()
+/// Regex matching the end of a definition start after the keyword, for `definition`.
+pub fn definition_regex() -> Regex {
+    Regex::new(r"^\s*(?:\((?P<category>[^)]+)\)\s*)?:").unwrap()
+}
+
+/// Test if an inline starts a keyword definition: "*kwd*: text" or "*kwd* (category): text".
+/// Returns the keyword, the category suffix if present, and the text after the colon.
+pub fn definition<'i>(
+    inline: &'i InlineElement,
+    regex: &Regex,
+) -> Option<(KeywordIndex, Option<&'i str>, &'i str)> {
+    let (range, keyword) = inline.tags.iter().find_map(|(range, tag)| match tag {
+        InlineTag::ExplicitKeyword(k) => Some((range, *k)),
+        _ => None,
+    })?;
+    if !inline.string[..range.start].trim().is_empty() {
+        return None;
     }
+    let captures = regex.captures(&inline.string[range.end..])?;
+    let category = captures.name("category").map(|m| m.as_str().trim());
+    let text = &inline.string[range.end + captures.get(0).unwrap().end()..];
+    Some((keyword, category, text))
 }
 
-fn section_dfs_mut<F: FnMut(&mut Section)>(s: &mut Section, f: &mut F) {
-    f(s);
-    for sub_section in &mut s.content.sub_sections {
-        section_dfs_mut(sub_section, f)
+fn find_keyword(
+    keywords: &KeywordSet,
+    aliases: &HashMap<UniCase<String>, KeywordIndex>,
+    name: &str,
+) -> Option<KeywordIndex> {
+    let name = UniCase::new(name.to_string());
+    match keywords.get_full(&name) {
+        Some((index, _)) => Some(index),
+        None => aliases.get(&name).cloned(),
     }
 }
 
-/// Build the regex used to find keywords in linear time.
-/// Return the regex, or None if the keyword set contains the empty string or is empty.
+/// Add implicit keyword tags for all matches of the keyword regex which do not overlap explicit ones.
+/// With accent folding, the regex is run on the text without accents.
+/// Matched names are resolved to keyword indexes by `find`.
+/// Tags are then sorted by range start.
+fn add_implicit_keyword_tags<F>(
+    inline: &mut InlineElement,
+    regex: &Regex,
+    accent_folding: bool,
+    find: F,
+) where
+    F: Fn(&str) -> Option<KeywordIndex>,
+{
+    let (text, original_offsets) = match accent_folding {
+        true => fold_accents(&inline.string),
+        false => (inline.string.clone(), (0..=inline.string.len()).collect()),
+    };
+    let overlaps_explicit = |tags: &[(std::ops::Range<usize>, InlineTag)], start, end| {
+        tags.iter().any(|(r, tag)| match tag {
+            InlineTag::ExplicitKeyword(_) => r.start < end && start < r.end,
+            _ => false,
+        })
+    };
+    let mut implicit_tags = Vec::new();
+    for m in regex.find_iter(&text) {
+        let start = original_offsets[m.start()];
+        let end = original_offsets[m.end()];
+        if overlaps_explicit(&inline.tags, start, end) {
+            continue;
+        }
+        // Regex matching uses the same normalization as names, so lookup should not fail.
+        if let Some(index) = find(m.as_str()) {
+            implicit_tags.push((start..end, InlineTag::ImplicitKeyword(index)))
+        }
+    }
+    inline.tags.extend(implicit_tags);
+    inline.tags.sort_by_key(|(r, _)| (r.start, r.end))
+}
+
+/// Call f on all text units of the document in order of appearance.
+/// Arguments of f are: section index path, kind of unit, inlines of the unit.
+pub fn for_each_text_unit<'d, F>(document: &'d Document, f: &mut F)
+where
+    F: FnMut(&[usize], TextUnitKind, &'d [InlineElement]),
+{
+    fn content_units<'d, F>(content: &'d SectionContent, path: &mut Vec<usize>, f: &mut F)
+    where
+        F: FnMut(&[usize], TextUnitKind, &'d [InlineElement]),
+    {
+        block_units(&content.blocks, path, f);
+        for (i, section) in content.sub_sections.iter().enumerate() {
+            path.push(i);
+            let title = slice::from_ref(&section.title);
+            f(path, TextUnitKind::Title(path.len()), title);
+            content_units(&section.content, path, f);
+            path.pop();
+        }
+    }
+    fn block_units<'d, F>(blocks: &'d [BlockElement], path: &[usize], f: &mut F)
+    where
+        F: FnMut(&[usize], TextUnitKind, &'d [InlineElement]),
+    {
+        for block in blocks {
+            match block {
+                BlockElement::Paragraph(inlines) => f(path, TextUnitKind::Paragraph, inlines),
+                BlockElement::Rule | BlockElement::Code { .. } => (),
+                BlockElement::List(list) => list_units(list, 0, path, f),
+                BlockElement::Quote(blocks) => block_units(blocks, path, f),
+                BlockElement::Table(table) => {
+                    let rows = table.rows.iter().map(|row| (false, row));
+                    for (header, row) in std::iter::once((true, &table.header)).chain(rows) {
+                        for cell in row.iter().filter(|cell| !cell.is_empty()) {
+                            f(path, TextUnitKind::TableCell { header }, cell)
+                        }
+                    }
+                }
+            }
+        }
+    }
+    fn list_units<'d, F>(list: &'d List, depth: usize, path: &[usize], f: &mut F)
+    where
+        F: FnMut(&[usize], TextUnitKind, &'d [InlineElement]),
+    {
+        let kind = TextUnitKind::ListItem {
+            ordered: list.ordered,
+            depth,
+        };
+        for item in &list.items {
+            f(path, kind, &item.text_content);
+            if let Some(sub_list) = &item.sub_list {
+                list_units(sub_list, depth + 1, path, f)
+            }
+        }
+    }
+    content_units(&document.content, &mut Vec::new(), f)
+}
+
+/// Mutable version of `for_each_text_unit`.
+pub fn for_each_text_unit_mut<F>(document: &mut Document, f: &mut F)
+where
+    F: FnMut(&[usize], TextUnitKind, &mut [InlineElement]),
+{
+    fn content_units<F>(content: &mut SectionContent, path: &mut Vec<usize>, f: &mut F)
+    where
+        F: FnMut(&[usize], TextUnitKind, &mut [InlineElement]),
+    {
+        block_units(&mut content.blocks, path, f);
+        for (i, section) in content.sub_sections.iter_mut().enumerate() {
+            path.push(i);
+            let title = slice::from_mut(&mut section.title);
+            f(path, TextUnitKind::Title(path.len()), title);
+            content_units(&mut section.content, path, f);
+            path.pop();
+        }
+    }
+    fn block_units<F>(blocks: &mut [BlockElement], path: &[usize], f: &mut F)
+    where
+        F: FnMut(&[usize], TextUnitKind, &mut [InlineElement]),
+    {
+        for block in blocks {
+            match block {
+                BlockElement::Paragraph(inlines) => f(path, TextUnitKind::Paragraph, inlines),
+                BlockElement::Rule | BlockElement::Code { .. } => (),
+                BlockElement::List(list) => list_units(list, 0, path, f),
+                BlockElement::Quote(blocks) => block_units(blocks, path, f),
+                BlockElement::Table(table) => {
+                    let rows = table.rows.iter_mut().map(|row| (false, row));
+                    for (header, row) in std::iter::once((true, &mut table.header)).chain(rows) {
+                        for cell in row.iter_mut().filter(|cell| !cell.is_empty()) {
+                            f(path, TextUnitKind::TableCell { header }, cell)
+                        }
+                    }
+                }
+            }
+        }
+    }
+    fn list_units<F>(list: &mut List, depth: usize, path: &[usize], f: &mut F)
+    where
+        F: FnMut(&[usize], TextUnitKind, &mut [InlineElement]),
+    {
+        let kind = TextUnitKind::ListItem {
+            ordered: list.ordered,
+            depth,
+        };
+        for item in &mut list.items {
+            f(path, kind, &mut item.text_content);
+            if let Some(sub_list) = &mut item.sub_list {
+                list_units(sub_list, depth + 1, path, f)
+            }
+        }
+    }
+    content_units(&mut document.content, &mut Vec::new(), f)
+}
+
+/// Build the regex used to find keyword names (keywords or aliases) in linear time.
+/// Return the regex, or None if the name list contains the empty string or is empty.
 ///
 /// The regex is built like "\b(kwd1|kwd2|...|kwdN)\b" and will be run on all inline text.
 /// It matches when one of the keywords is found on word boundaries.
 /// This avoids matching word prefixes, like "hell" in "hello world".
 /// Matches are non overlapping so extracted keywords will be non overlapping.
 /// Lastly, keywords in the alternate part are ordered by decreasing length to prefer the biggest valid matches.
-fn keyword_search_regex(keywords: &KeywordSet) -> Option<Regex> {
-    let mut keyword_list: Vec<&str> = keywords.iter().map(|s| s.as_ref()).collect();
+fn keyword_search_regex<'k, I>(names: I, case_insensitive: bool) -> Option<Regex>
+where
+    I: Iterator<Item = &'k str>,
+{
+    let mut keyword_list: Vec<&str> = names.collect();
     keyword_list.sort_unstable_by_key(|s| -(s.len() as i64));
-    if keyword_list.last().map_or(true, |s| s.len() == 0) {
+    if keyword_list.last().map_or(true, |s| s.is_empty()) {
         return None; // Fail if empty list of empty string in list
     }
     let keyword_list = keyword_list.into_iter().map(escape_regex_special_chars);
     let regex_str = format!(r"\b({})\b", join(keyword_list, "|"));
     let regex = RegexBuilder::new(&regex_str)
-        .case_insensitive(true)
+        .case_insensitive(case_insensitive)
         .unicode(true)
         .build()
         .expect("Keyword regex construction");
     Some(regex)
 }
 
+/// Slug of a text: accents folded, lower case, other chars than letters and digits replaced by '-'.
+/// Empty if the text has no letter or digit.
+pub fn slugify(text: &str) -> String {
+    let (folded, _) = fold_accents(text);
+    let words: Vec<String> = folded
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect();
+    words.join("-")
+}
+
+/// Slugs of names, or the default if empty.
+/// Duplicate slugs get a numeric suffix in order: "a", "a-2", "a-3".
+fn unique_slugs<'a, I: Iterator<Item = &'a str>>(names: I, default: &str) -> Vec<String> {
+    let mut used = HashSet::new();
+    names
+        .map(|name| {
+            let base = match slugify(name) {
+                slug if slug.is_empty() => default.to_string(),
+                slug => slug,
+            };
+            let mut slug = base.clone();
+            let mut n = 1;
+            while used.contains(&slug) {
+                n += 1;
+                slug = format!("{}-{}", base, n)
+            }
+            used.insert(slug.clone());
+            slug
+        })
+        .collect()
+}
+
+/// Ids used by the page templates, which section slugs must not take.
+const RESERVED_SLUGS: &[&str] = &["document", "search", "search-results"];
+
+/// Slug of each section from its title, or "section" if empty.
+/// Duplicate slugs get a numeric suffix in document order: "a", "a-2", "a-3".
+/// Reserved slugs are taken as already used.
+fn section_slugs(content: &SectionContent) -> HashMap<Vec<usize>, String> {
+    fn visit(
+        content: &SectionContent,
+        path: &mut Vec<usize>,
+        used: &mut HashSet<String>,
+        slugs: &mut HashMap<Vec<usize>, String>,
+    ) {
+        for (i, section) in content.sub_sections.iter().enumerate() {
+            path.push(i);
+            let base = match slugify(&section.title.string) {
+                slug if slug.is_empty() => "section".to_string(),
+                slug => slug,
+            };
+            let mut slug = base.clone();
+            let mut n = 1;
+            while used.contains(&slug) {
+                n += 1;
+                slug = format!("{}-{}", base, n)
+            }
+            used.insert(slug.clone());
+            slugs.insert(path.clone(), slug);
+            visit(&section.content, path, used, slugs);
+            path.pop();
+        }
+    }
+    let mut slugs = HashMap::new();
+    let mut used = RESERVED_SLUGS.iter().map(|s| s.to_string()).collect();
+    visit(content, &mut Vec::new(), &mut used, &mut slugs);
+    slugs
+}
+
+/// Remove accents from latin letters.
+/// Returns the folded text, and for each of its byte offsets (plus end), the offset in the original text.
+pub fn fold_accents(text: &str) -> (String, Vec<usize>) {
+    let mut folded = String::with_capacity(text.len());
+    let mut original_offsets = Vec::with_capacity(text.len() + 1);
+    for (offset, c) in text.char_indices() {
+        let start = folded.len();
+        match fold_accent(c) {
+            Some(s) => folded.push_str(s),
+            None => folded.push(c),
+        }
+        original_offsets.extend(std::iter::repeat(offset).take(folded.len() - start))
+    }
+    original_offsets.push(text.len());
+    (folded, original_offsets)
+}
+
+fn fold_accent(c: char) -> Option<&'static str> {
+    Some(match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' => "a",
+        'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' => "A",
+        'ç' => "c",
+        'Ç' => "C",
+        'è' | 'é' | 'ê' | 'ë' => "e",
+        'È' | 'É' | 'Ê' | 'Ë' => "E",
+        'ì' | 'í' | 'î' | 'ï' => "i",
+        'Ì' | 'Í' | 'Î' | 'Ï' => "I",
+        'ñ' => "n",
+        'Ñ' => "N",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' => "o",
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø' => "O",
+        'ù' | 'ú' | 'û' | 'ü' => "u",
+        'Ù' | 'Ú' | 'Û' | 'Ü' => "U",
+        'ý' | 'ÿ' => "y",
+        'Ý' | 'Ÿ' => "Y",
+        'æ' => "ae",
+        'Æ' => "AE",
+        'œ' => "oe",
+        'Œ' => "OE",
+        _ => return None,
+    })
+}
+
 fn join<I>(mut iter: I, sep: &str) -> String
 where
     I: Iterator,
@@ -93,3 +666,144 @@ where
         }
     }
 }
+
+#[test]
+fn categories() {
+    let text = "*a*: top\n# Places #\n- *b*: text\n- *c* (Item) : text\n- *d*, not a definition\n\n*a* (place): redefinition\n";
+    let (document, keywords) = parse(text).unwrap();
+    let document = IndexedDocument::from(document, keywords);
+    let category_of = |k: &str| {
+        let keyword = document.keyword_index(k).unwrap();
+        document
+            .keyword_category(keyword)
+            .map(|c| document.category(c))
+    };
+    assert_eq!(category_of("a"), Some("place"));
+    assert_eq!(category_of("b"), Some("Places"));
+    assert_eq!(category_of("c"), Some("Item"));
+    assert_eq!(category_of("d"), None);
+    assert_eq!(document.categories().len(), 3);
+}
+
+#[test]
+fn matching_options() {
+    let text = "*Général* *le* *Aa*\n\ngeneral le aa GENERAL\n";
+    let index = |options: &MatchingOptions| {
+        let (document, keywords) = parse(text).unwrap();
+        IndexedDocument::with_options(document, keywords, options, &[])
+    };
+    let implicit_count = |document: &IndexedDocument, k: &str| {
+        let keyword = document.keyword_index(k).unwrap();
+        document.implicit_occurrences(keyword).len()
+    };
+    let document = index(&MatchingOptions::default());
+    assert_eq!(implicit_count(&document, "général"), 0);
+    assert_eq!(implicit_count(&document, "le"), 1);
+    let document = index(&MatchingOptions {
+        accent_folding: true,
+        stop_list: vec!["LE".into()],
+        min_length: 3,
+        ..MatchingOptions::default()
+    });
+    assert_eq!(implicit_count(&document, "général"), 1);
+    assert_eq!(implicit_count(&document, "le"), 0);
+    assert_eq!(implicit_count(&document, "aa"), 0);
+    let document = index(&MatchingOptions {
+        case_folding: false,
+        ..MatchingOptions::default()
+    });
+    assert_eq!(implicit_count(&document, "le"), 1);
+    assert_eq!(implicit_count(&document, "aa"), 0);
+
+    assert_eq!(fold_accents("aœb").0, "aoeb");
+    assert_eq!(fold_accents("aœb").1, vec![0, 1, 1, 3, 4]);
+}
+
+#[test]
+fn aliases() {
+    let text = "---\naliases:\n  Aldric: [Al, le chevalier]\n---\nLe Chevalier et *Al*.\n";
+    let (document, keywords) = parse(text).unwrap();
+    let document = IndexedDocument::from(document, keywords);
+    let aldric = document.keyword_index("aldric").unwrap();
+    assert_eq!(document.keyword_index("al"), Some(aldric));
+    assert_eq!(document.explicit_occurrences(aldric), &[0]);
+    assert_eq!(document.implicit_occurrences(aldric), &[0]);
+}
+
+#[test]
+fn indexing() {
+    let text = "# Wimd #\n*Wimd* is a tool.\n\n- uses *rust*\n- wimd and Rust\n";
+    let (document, keywords) = parse(text).unwrap();
+    let document = IndexedDocument::from(document, keywords);
+    let wimd = document.keyword_index("WIMD").unwrap();
+    let rust = document.keyword_index("rust").unwrap();
+    assert_eq!(document.explicit_occurrences(wimd), &[1]);
+    assert_eq!(document.implicit_occurrences(wimd), &[0, 3]);
+    assert_eq!(document.explicit_occurrences(rust), &[2]);
+    assert_eq!(document.implicit_occurrences(rust), &[3]);
+    assert_eq!(document.inline_location(3).section, vec![0]);
+    assert_eq!(document.inline_location(3).unit, 3);
+    assert_eq!(document.section_breadcrumb(&[0]), vec!["Wimd"]);
+    assert_eq!(document.section_display_name(&[]), "(top)");
+    let sections: Vec<Vec<usize>> = document.referencing_sections(wimd).into_iter().collect();
+    assert_eq!(sections, vec![vec![0]]);
+}
+
+#[test]
+fn tables() {
+    let text = "Wimd\n\n| *Rust* | Notes |\n|---|:-:|\n| wimd | rust *wimd* |\n|  | |\n";
+    let (document, keywords) = parse(text).unwrap();
+    let document = IndexedDocument::from(document, keywords);
+    let rust = document.keyword_index("rust").unwrap();
+    let wimd = document.keyword_index("wimd").unwrap();
+    assert_eq!(document.explicit_occurrences(rust), &[1]);
+    assert_eq!(document.implicit_occurrences(rust), &[4]);
+    assert_eq!(document.explicit_occurrences(wimd), &[4]);
+    assert_eq!(document.implicit_occurrences(wimd), &[0, 3]);
+    let mut kinds = Vec::new();
+    for_each_text_unit(document.document(), &mut |_, kind, _| kinds.push(kind));
+    let cell = |header| TextUnitKind::TableCell { header };
+    assert_eq!(
+        kinds,
+        vec![
+            TextUnitKind::Paragraph,
+            cell(true),
+            cell(true),
+            cell(false),
+            cell(false)
+        ]
+    );
+    match &document.document().content.blocks[1] {
+        BlockElement::Table(table) => {
+            assert_eq!(table.alignments, vec![Alignment::None, Alignment::Center]);
+            assert_eq!(table.rows.len(), 2);
+            assert!(table.rows[1].iter().all(Vec::is_empty))
+        }
+        block => panic!("Expected table: {:?}", block),
+    }
+}
+
+#[test]
+fn slugs() {
+    let text = "# Général #\n## Les *Lieux* ! #\n# Général #\n# général 2 #\n# 日本 #\n# !! #\n# Document #\n# Search results #\n";
+    let (document, keywords) = crate::ast::parse(text).unwrap();
+    let document = IndexedDocument::from(document, keywords);
+    let slugs: Vec<&str> = [&[0][..], &[0, 0], &[1], &[2], &[3], &[4], &[5], &[6]]
+        .iter()
+        .map(|path| document.section_slug(path).unwrap())
+        .collect();
+    assert_eq!(
+        slugs,
+        vec![
+            "general",
+            "les-lieux",
+            "general-2",
+            "general-2-2",
+            "日本",
+            "section",
+            "document-2",
+            "search-results-2"
+        ]
+    );
+    assert_eq!(document.section_slug(&[]), None);
+}
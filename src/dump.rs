@@ -0,0 +1,189 @@
+use crate::ast::*;
+use std::fmt::{self, Write};
+use std::ops::Range;
+
+/******************************************************************************
+ * Debug dump of the ast.
+ *
+ * The section tree is printed with one line per element, indented by depth.
+ * Each inline is printed with its index, line and text, followed by one line per tag in stored order.
+ * Tag lines underline the tag range beneath the text:
+ * - '^' for explicit keywords, '~' for implicit keywords, '=' for highlights, '?' for secret ranges.
+ * Ranges are byte ranges in the inline string; invalid ranges are reported instead of underlined.
+ */
+
+const INDENT: &str = "  ";
+
+pub fn write_document<W: Write>(
+    out: &mut W,
+    document: &Document,
+    keywords: &KeywordSet,
+) -> fmt::Result {
+    writeln!(out, "Document")?;
+    for span in &document.sources {
+        writeln!(
+            out,
+            "{}Source {}: line {} (source line {})",
+            INDENT, span.name, span.first_line, span.source_first_line
+        )?;
+    }
+    write_section_content(out, keywords, &document.content, 1)
+}
+
+fn write_section_content<W: Write>(
+    out: &mut W,
+    keywords: &KeywordSet,
+    content: &SectionContent,
+    depth: usize,
+) -> fmt::Result {
+    let indent = INDENT.repeat(depth);
+    write_blocks(out, keywords, &content.blocks, depth)?;
+    for section in &content.sub_sections {
+        writeln!(out, "{}Section", indent)?;
+        write_inline(out, keywords, &section.title, depth + 1)?;
+        write_section_content(out, keywords, &section.content, depth + 1)?
+    }
+    Ok(())
+}
+
+fn write_blocks<W: Write>(
+    out: &mut W,
+    keywords: &KeywordSet,
+    blocks: &[BlockElement],
+    depth: usize,
+) -> fmt::Result {
+    let indent = INDENT.repeat(depth);
+    for block in blocks {
+        match block {
+            BlockElement::Paragraph(inlines) => {
+                writeln!(out, "{}Paragraph", indent)?;
+                for inline in inlines {
+                    write_inline(out, keywords, inline, depth + 1)?
+                }
+            }
+            BlockElement::Rule => writeln!(out, "{}Rule", indent)?,
+            BlockElement::List(list) => write_list(out, keywords, list, depth)?,
+            BlockElement::Code {
+                lang, text, secret, ..
+            } => {
+                write!(out, "{}Code", indent)?;
+                if let Some(lang) = lang {
+                    write!(out, " ({})", lang)?
+                }
+                match secret {
+                    Some(audiences) => writeln!(out, " {}", format_secret_marker(audiences))?,
+                    None => writeln!(out)?,
+                }
+                for line in text.lines() {
+                    writeln!(out, "{}{}| {}", indent, INDENT, line)?
+                }
+            }
+            BlockElement::Quote(blocks) => {
+                writeln!(out, "{}Quote", indent)?;
+                write_blocks(out, keywords, blocks, depth + 1)?
+            }
+            BlockElement::Table(table) => {
+                writeln!(out, "{}Table {:?}", indent, table.alignments)?;
+                let rows = table.rows.iter().map(|row| ("Row", row));
+                for (name, row) in std::iter::once(("Header", &table.header)).chain(rows) {
+                    writeln!(out, "{}{}{}", indent, INDENT, name)?;
+                    for cell in row {
+                        writeln!(out, "{}{}Cell", indent, INDENT.repeat(2))?;
+                        for inline in cell {
+                            write_inline(out, keywords, inline, depth + 3)?
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_list<W: Write>(
+    out: &mut W,
+    keywords: &KeywordSet,
+    list: &List,
+    depth: usize,
+) -> fmt::Result {
+    let indent = INDENT.repeat(depth);
+    let kind = if list.ordered { "ordered" } else { "unordered" };
+    writeln!(out, "{}List ({})", indent, kind)?;
+    for item in &list.items {
+        writeln!(out, "{}{}Item", indent, INDENT)?;
+        for inline in &item.text_content {
+            write_inline(out, keywords, inline, depth + 2)?
+        }
+        if let Some(sub_list) = &item.sub_list {
+            write_list(out, keywords, sub_list, depth + 2)?
+        }
+    }
+    Ok(())
+}
+
+fn write_inline<W: Write>(
+    out: &mut W,
+    keywords: &KeywordSet,
+    inline: &InlineElement,
+    depth: usize,
+) -> fmt::Result {
+    let prefix = format!(
+        "{}#{} line {}: ",
+        INDENT.repeat(depth),
+        inline.index,
+        inline.line
+    );
+    writeln!(out, "{}{}", prefix, inline.string)?;
+    let margin = " ".repeat(prefix.chars().count());
+    for (range, tag) in &inline.tags {
+        let (marker, description) = match tag {
+            InlineTag::Highlight => ('=', "highlight".to_string()),
+            InlineTag::Secret(audiences) => ('?', format_secret_marker(audiences)),
+            InlineTag::ExplicitKeyword(k) => ('^', format!("explicit {}", keyword(keywords, *k))),
+            InlineTag::ImplicitKeyword(k) => ('~', format!("implicit {}", keyword(keywords, *k))),
+        };
+        match underline(&inline.string, range, marker) {
+            Some(underline) => writeln!(out, "{}{} {}", margin, underline, description)?,
+            None => writeln!(out, "{}invalid range {:?} {}", margin, range, description)?,
+        }
+    }
+    Ok(())
+}
+
+fn keyword(keywords: &KeywordSet, index: usize) -> &str {
+    keywords
+        .get_index(index)
+        .map_or("<unknown>", |k| k.as_ref())
+}
+
+/// Underline a byte range of the text using char columns, or None if the range is not valid.
+fn underline(text: &str, range: &Range<usize>, marker: char) -> Option<String> {
+    let before = text.get(..range.start)?;
+    let tagged = text.get(range.clone())?;
+    let mut line = " ".repeat(before.chars().count());
+    line.extend(std::iter::repeat(marker).take(tagged.chars().count().max(1)));
+    Some(line)
+}
+
+#[test]
+fn dump() {
+    let (document, keywords) = parse("# T #\né **b *c* d**\n\n- *i*\n").unwrap();
+    let mut out = String::new();
+    write_document(&mut out, &document, &keywords).unwrap();
+    let expected = "\
+Document
+  Source <text>: line 1 (source line 1)
+  Section
+    #0 line 1: T
+    Paragraph
+      #1 line 2: é b c d
+                     ^ explicit c
+                   ===== highlight
+    List (unordered)
+      Item
+        #2 line 4: i
+                   ^ explicit i
+";
+    assert_eq!(out, expected);
+    assert_eq!(underline("ab", &(1..3), '^'), None);
+}
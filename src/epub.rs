@@ -0,0 +1,337 @@
+use crate::ast::SectionContent;
+use crate::config::Config;
+use crate::document::IndexedDocument;
+use crate::template::escape;
+use crate::wiki::{self, Theme};
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::Write;
+use std::hash::{Hash, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/******************************************************************************
+ * EPUB 3 export of the html wiki.
+ *
+ * The EPUB contains the wiki pages as XHTML (see wiki), without search:
+ * - mimetype, META-INF/container.xml: fixed EPUB files, pointing to the package document.
+ * - content.opf: package document, with metadata from front matter, manifest of pages and reading order.
+ * - nav.xhtml: navigation document, with the section tree of the document and the alphabetical keyword list.
+ * - style.css, index.html, keywords.html, keyword and category pages.
+ *
+ * The EPUB container is a zip archive. Entries are stored without compression,
+ * which is simple and required for the mimetype entry that must come first.
+ * The book identifier is derived from front matter title and author, to be stable across exports.
+ */
+
+const CONTAINER: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+<rootfiles>
+<rootfile full-path="content.opf" media-type="application/oebps-package+xml"/>
+</rootfiles>
+</container>
+"#;
+
+/// EPUB archive of the wiki.
+pub fn epub(document: &IndexedDocument, config: &Config, theme: &Theme) -> Vec<u8> {
+    let pages = wiki::render_epub_pages(document, config, theme);
+    let mut archive = ZipWriter::default();
+    archive.add("mimetype", b"application/epub+zip");
+    archive.add("META-INF/container.xml", CONTAINER.as_bytes());
+    archive.add("content.opf", package(document, &pages).as_bytes());
+    archive.add("nav.xhtml", navigation(document).as_bytes());
+    for (name, page) in &pages {
+        archive.add(name, page.as_bytes())
+    }
+    archive.finish()
+}
+
+/// Package document: metadata, manifest and spine.
+fn package(document: &IndexedDocument, pages: &[(String, String)]) -> String {
+    let front_matter = &document.document().front_matter;
+    let language = front_matter.language.as_deref().unwrap_or("en");
+    let mut hasher = DefaultHasher::new();
+    (&front_matter.title, &front_matter.author).hash(&mut hasher);
+
+    let mut out = String::new();
+    writeln!(out, r#"<?xml version="1.0" encoding="utf-8"?>"#).unwrap();
+    writeln!(
+        out,
+        r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id">"#
+    )
+    .unwrap();
+    writeln!(
+        out,
+        r#"<metadata xmlns:dc="http://purl.org/dc/elements/1.1/">"#
+    )
+    .unwrap();
+    let identifier = format!("urn:wimd:{:016x}", hasher.finish());
+    writeln!(
+        out,
+        r#"<dc:identifier id="book-id">{}</dc:identifier>"#,
+        identifier
+    )
+    .unwrap();
+    let title = escape(wiki::document_title(document));
+    writeln!(out, "<dc:title>{}</dc:title>", title).unwrap();
+    writeln!(out, "<dc:language>{}</dc:language>", escape(language)).unwrap();
+    if let Some(author) = &front_matter.author {
+        writeln!(out, "<dc:creator>{}</dc:creator>", escape(author)).unwrap();
+    }
+    let modified = utc_timestamp(SystemTime::now());
+    writeln!(
+        out,
+        r#"<meta property="dcterms:modified">{}</meta>"#,
+        modified
+    )
+    .unwrap();
+    writeln!(out, "</metadata>\n<manifest>").unwrap();
+    writeln!(
+        out,
+        r#"<item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>"#
+    )
+    .unwrap();
+    for (name, _) in pages {
+        let media_type = if name.ends_with(".css") {
+            "text/css"
+        } else {
+            "application/xhtml+xml"
+        };
+        writeln!(
+            out,
+            r#"<item id="{}" href="{}" media-type="{}"/>"#,
+            item_id(name),
+            name,
+            media_type
+        )
+        .unwrap();
+    }
+    writeln!(out, "</manifest>\n<spine>").unwrap();
+    for (name, _) in pages.iter().filter(|(name, _)| name.ends_with(".html")) {
+        writeln!(out, r#"<itemref idref="{}"/>"#, item_id(name)).unwrap();
+    }
+    writeln!(out, "</spine>\n</package>").unwrap();
+    out
+}
+
+/// Manifest item id from a page name: "keyword_tour.html" gives "keyword_tour", "style.css" gives "style".
+fn item_id(name: &str) -> &str {
+    name.split('.').next().unwrap()
+}
+
+/// Navigation document, with the section tree and the keyword list.
+fn navigation(document: &IndexedDocument) -> String {
+    fn write_sections(
+        out: &mut String,
+        document: &IndexedDocument,
+        content: &SectionContent,
+        path: &mut Vec<usize>,
+    ) {
+        if content.sub_sections.is_empty() {
+            return;
+        }
+        writeln!(out, "<ol>").unwrap();
+        for (i, section) in content.sub_sections.iter().enumerate() {
+            path.push(i);
+            write!(
+                out,
+                r##"<li><a href="index.html#{}">{}</a>"##,
+                document.section_slug(path).unwrap(),
+                escape(&section.title.string)
+            )
+            .unwrap();
+            write_sections(out, document, &section.content, path);
+            writeln!(out, "</li>").unwrap();
+            path.pop();
+        }
+        writeln!(out, "</ol>").unwrap();
+    }
+
+    let mut out = String::new();
+    out.push_str(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<head>
+<meta charset="utf-8"/>
+<title>Contents</title>
+</head>
+<body>
+<nav epub:type="toc" id="toc">
+<h1>Contents</h1>
+<ol>
+"#,
+    );
+    let title = escape(wiki::document_title(document));
+    write!(out, r#"<li><a href="index.html">{}</a>"#, title).unwrap();
+    write_sections(
+        &mut out,
+        document,
+        &document.document().content,
+        &mut Vec::new(),
+    );
+    writeln!(out, "</li>").unwrap();
+    write!(out, r#"<li><a href="keywords.html">Keywords</a>"#).unwrap();
+    let keywords = wiki::sorted_keywords(document, (0..document.keywords().len()).collect());
+    if !keywords.is_empty() {
+        writeln!(out, "<ol>").unwrap();
+        for keyword in keywords {
+            writeln!(
+                out,
+                r#"<li><a href="{}">{}</a></li>"#,
+                wiki::keyword_page_name(document, keyword),
+                escape(document.keyword(keyword))
+            )
+            .unwrap();
+        }
+        writeln!(out, "</ol>").unwrap();
+    }
+    writeln!(out, "</li>\n</ol>\n</nav>\n</body>\n</html>").unwrap();
+    out
+}
+
+/// Time formatted as required for dcterms:modified: "2019-07-31T12:00:00Z".
+fn utc_timestamp(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, seconds) = (seconds / 86400, seconds % 86400);
+    // Civil date from days since epoch, from http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let (era, day_of_era) = (z / 146_097, z % 146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = era * 400 + year_of_era + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Zip archive with stored entries.
+#[derive(Default)]
+struct ZipWriter {
+    data: Vec<u8>,
+    central_directory: Vec<u8>,
+    entries: u16,
+}
+
+/// Modification date of entries, in MS-DOS format: 1980-01-01, for reproducible archives.
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+impl ZipWriter {
+    fn add(&mut self, name: &str, content: &[u8]) {
+        let offset = self.data.len() as u32;
+        let crc = crc32(content);
+        let size = content.len() as u32;
+        // Local file header
+        push_u32(&mut self.data, 0x0403_4b50);
+        self.data
+            .extend_from_slice(&Self::common_header(name, crc, size));
+        self.data.extend_from_slice(name.as_bytes());
+        self.data.extend_from_slice(content);
+        // Central directory header
+        let cd = &mut self.central_directory;
+        push_u32(cd, 0x0201_4b50);
+        push_u16(cd, 20); // Version made by
+        cd.extend_from_slice(&Self::common_header(name, crc, size));
+        push_u16(cd, 0); // Comment length
+        push_u16(cd, 0); // Disk number
+        push_u16(cd, 0); // Internal attributes
+        push_u32(cd, 0); // External attributes
+        push_u32(cd, offset);
+        cd.extend_from_slice(name.as_bytes());
+        self.entries += 1
+    }
+
+    /// Header fields from version needed to extra field length, shared by local and central headers.
+    fn common_header(name: &str, crc: u32, size: u32) -> Vec<u8> {
+        let mut header = Vec::new();
+        push_u16(&mut header, 10); // Version needed
+        push_u16(&mut header, 1 << 11); // Flags: UTF-8 file names
+        push_u16(&mut header, 0); // Compression: stored
+        push_u16(&mut header, 0); // Time
+        push_u16(&mut header, DOS_DATE);
+        push_u32(&mut header, crc);
+        push_u32(&mut header, size); // Compressed size
+        push_u32(&mut header, size);
+        push_u16(&mut header, name.len() as u16);
+        push_u16(&mut header, 0); // Extra field length
+        header
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let offset = self.data.len() as u32;
+        let size = self.central_directory.len() as u32;
+        self.data.append(&mut self.central_directory);
+        // End of central directory record
+        push_u32(&mut self.data, 0x0605_4b50);
+        push_u16(&mut self.data, 0); // Disk number
+        push_u16(&mut self.data, 0); // Disk of central directory
+        push_u16(&mut self.data, self.entries);
+        push_u16(&mut self.data, self.entries);
+        push_u32(&mut self.data, size);
+        push_u32(&mut self.data, offset);
+        push_u16(&mut self.data, 0); // Comment length
+        self.data
+    }
+}
+
+fn push_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes())
+}
+fn push_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes())
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask)
+        }
+    }
+    !crc
+}
+
+#[test]
+fn epub_archive() {
+    assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    assert_eq!(
+        utc_timestamp(UNIX_EPOCH + std::time::Duration::from_secs(951_827_696)),
+        "2000-02-29T12:34:56Z"
+    );
+
+    let (document, keywords) = crate::ast::parse("# Lieux #\nLa *Tour*.\n## Cave ##\n").unwrap();
+    let document = IndexedDocument::from(document, keywords);
+    let nav = navigation(&document);
+    let expected = r##"<li><a href="index.html">Document</a><ol>
+<li><a href="index.html#lieux">Lieux</a><ol>
+<li><a href="index.html#cave">Cave</a></li>
+</ol>
+</li>
+</ol>
+</li>
+<li><a href="keywords.html">Keywords</a><ol>
+<li><a href="keyword_tour.html">Tour</a></li>
+</ol>
+</li>
+"##;
+    assert!(nav.contains(expected));
+
+    let config = Config::default_in(".".into());
+    let theme = Theme::load(&config).unwrap();
+    let archive = epub(&document, &config, &theme);
+    // The mimetype entry comes first, stored, with its name at offset 30 and content after it
+    assert_eq!(&archive[..4], b"PK\x03\x04");
+    assert_eq!(&archive[30..58], b"mimetypeapplication/epub+zip");
+    // Entry count in the end of central directory record: fixed files, style and 3 pages
+    assert_eq!(&archive[archive.len() - 12..archive.len() - 10], &[8, 0]);
+}
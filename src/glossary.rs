@@ -0,0 +1,238 @@
+use crate::ast::*;
+use crate::document::TextUnitKind;
+use crate::document::{definition, definition_regex};
+use crate::document::{fold_accents, for_each_text_unit, IndexedDocument, KeywordIndex};
+use crate::search::sentence_ranges;
+use crate::template::escape;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+
+/******************************************************************************
+ * Glossary of keywords.
+ *
+ * All keywords are listed alphabetically, with a description:
+ * - the definition of the keyword if one is recognised, from a paragraph or list item starting with
+ *   an explicit occurrence followed by a colon: "*Mira* : text" or "*Mira* (category) : text",
+ *   as recognised by the document module for categories. If nothing follows the colon,
+ *   the items of the following list (or sub list for list items) are the definition, joined by ';'.
+ * - otherwise the sentence of its first explicit occurrence, outside of section titles.
+ *
+ * Keywords are sorted using French dictionary order: case, accents and punctuation are ignored first,
+ * then unaccented letters come before accented ones, and then lower case before upper case.
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlossaryFormat {
+    Markdown,
+    Html,
+}
+
+impl GlossaryFormat {
+    pub fn from_name(name: &str) -> Option<GlossaryFormat> {
+        match name {
+            "markdown" => Some(GlossaryFormat::Markdown),
+            "html" => Some(GlossaryFormat::Html),
+            _ => None,
+        }
+    }
+}
+
+/// Keywords in alphabetical order, with their description if any.
+pub fn glossary(document: &IndexedDocument) -> Vec<(KeywordIndex, Option<String>)> {
+    let mut descriptions = definitions(document.document());
+    for_each_text_unit(document.document(), &mut |_, kind, inlines| {
+        if let TextUnitKind::Title(_) = kind {
+            return;
+        }
+        for inline in inlines {
+            for (range, tag) in &inline.tags {
+                if let InlineTag::ExplicitKeyword(k) = tag {
+                    descriptions.entry(*k).or_insert_with(|| {
+                        let sentence = sentence_ranges(&inline.string)
+                            .into_iter()
+                            .find(|sentence| sentence.contains(&range.start))
+                            .unwrap();
+                        inline.string[sentence].trim().to_string()
+                    });
+                }
+            }
+        }
+    });
+    let mut keywords: Vec<KeywordIndex> = (0..document.keywords().len()).collect();
+    keywords.sort_by_cached_key(|k| collation_key(document.keyword(*k)));
+    keywords
+        .into_iter()
+        .map(|k| (k, descriptions.remove(&k)))
+        .collect()
+}
+
+/// Sort key for French dictionary order.
+fn collation_key(name: &str) -> (String, String, Vec<bool>, String) {
+    let lower_case = name.to_lowercase();
+    let letters = fold_accents(&lower_case).0;
+    let letters = letters.chars().filter(|c| c.is_alphanumeric()).collect();
+    let case = name.chars().map(char::is_uppercase).collect();
+    (letters, lower_case, case, name.to_string())
+}
+
+/// First recognised definition of each keyword.
+fn definitions(document: &Document) -> HashMap<KeywordIndex, String> {
+    type Definitions = HashMap<KeywordIndex, String>;
+    fn content_definitions(content: &SectionContent, regex: &Regex, definitions: &mut Definitions) {
+        block_definitions(&content.blocks, regex, definitions);
+        for section in &content.sub_sections {
+            content_definitions(&section.content, regex, definitions)
+        }
+    }
+    fn block_definitions(blocks: &[BlockElement], regex: &Regex, definitions: &mut Definitions) {
+        for (i, block) in blocks.iter().enumerate() {
+            match block {
+                BlockElement::Paragraph(inlines) => {
+                    let next_list = match blocks.get(i + 1) {
+                        Some(BlockElement::List(list)) => Some(list),
+                        _ => None,
+                    };
+                    add_definition(definitions, regex, inlines, next_list)
+                }
+                BlockElement::Rule | BlockElement::Code { .. } | BlockElement::Table(_) => (),
+                BlockElement::List(list) => list_definitions(list, regex, definitions),
+                BlockElement::Quote(blocks) => block_definitions(blocks, regex, definitions),
+            }
+        }
+    }
+    fn list_definitions(list: &List, regex: &Regex, definitions: &mut Definitions) {
+        for item in &list.items {
+            add_definition(
+                definitions,
+                regex,
+                &item.text_content,
+                item.sub_list.as_ref(),
+            );
+            if let Some(sub_list) = &item.sub_list {
+                list_definitions(sub_list, regex, definitions)
+            }
+        }
+    }
+    let mut definitions = HashMap::new();
+    content_definitions(&document.content, &definition_regex(), &mut definitions);
+    definitions
+}
+
+/// Record the definition in a text unit, if it has the form "<kwd> : text" or "<kwd> :" before a list.
+fn add_definition(
+    definitions: &mut HashMap<KeywordIndex, String>,
+    regex: &Regex,
+    inlines: &[InlineElement],
+    list: Option<&List>,
+) {
+    let (keyword, _, rest) = match inlines.first().and_then(|first| definition(first, regex)) {
+        Some(definition) => definition,
+        None => return,
+    };
+    let mut definition =
+        join_lines(std::iter::once(rest).chain(inlines[1..].iter().map(|i| i.string.as_str())));
+    if definition.is_empty() {
+        if let Some(list) = list {
+            let items: Vec<String> = list
+                .items
+                .iter()
+                .map(|item| join_lines(item.text_content.iter().map(|i| i.string.as_str())))
+                .filter(|item| !item.is_empty())
+                .collect();
+            definition = items.join("; ")
+        }
+    }
+    if !definition.is_empty() {
+        definitions.entry(keyword).or_insert(definition);
+    }
+}
+
+fn join_lines<'s, I: Iterator<Item = &'s str>>(lines: I) -> String {
+    let lines: Vec<&str> = lines.map(str::trim).filter(|l| !l.is_empty()).collect();
+    lines.join(" ")
+}
+
+pub fn write_glossary<W: Write>(
+    out: &mut W,
+    document: &IndexedDocument,
+    format: GlossaryFormat,
+) -> fmt::Result {
+    let entries = glossary(document);
+    match format {
+        GlossaryFormat::Markdown => {
+            writeln!(out, "# Glossary\n")?;
+            for (keyword, description) in entries {
+                let name = document.keyword(keyword);
+                write!(
+                    out,
+                    "- **{}**",
+                    crate::markdown::escape(name, 0..name.len())
+                )?;
+                match description {
+                    Some(text) => {
+                        writeln!(out, ": {}", crate::markdown::escape(&text, 0..text.len()))?
+                    }
+                    None => writeln!(out)?,
+                }
+            }
+        }
+        GlossaryFormat::Html => {
+            writeln!(out, "<!DOCTYPE html>")?;
+            match &document.document().front_matter.language {
+                Some(language) => writeln!(out, "<html lang=\"{}\">", escape(language))?,
+                None => writeln!(out, "<html>")?,
+            }
+            writeln!(out, "<head>\n<meta charset=\"utf-8\">")?;
+            writeln!(out, "<title>Glossary</title>\n</head>\n<body>")?;
+            writeln!(out, "<h1>Glossary</h1>\n<dl>")?;
+            for (keyword, description) in entries {
+                writeln!(out, "<dt>{}</dt>", escape(document.keyword(keyword)))?;
+                if let Some(text) = description {
+                    writeln!(out, "<dd>{}</dd>", escape(&text))?
+                }
+            }
+            writeln!(out, "</dl>\n</body>\n</html>")?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn glossary_entries() {
+    let text = "\
+# Lieux #
+*Élan* : un cerf.
+
+*Aldric* (Personnage) : un guerrier.
+
+La *cote* monte. Une *Côte* sud.
+
+*Mira* :
+- une mage
+- rousse
+
+*Orage* est là. Ensuite *Orage* : menace.
+Rien sur *Été*
+
+- *Ville* : une cité
+  sur la *côte*
+";
+    let (document, keywords) = crate::ast::parse(text).unwrap();
+    let document = IndexedDocument::from(document, keywords);
+    let entries: Vec<(&str, Option<String>)> = glossary(&document)
+        .into_iter()
+        .map(|(k, description)| (document.keyword(k), description))
+        .collect();
+    let expected = vec![
+        ("Aldric", Some("un guerrier.".to_string())),
+        ("cote", Some("La cote monte.".to_string())),
+        ("Côte", Some("Une Côte sud.".to_string())),
+        ("Élan", Some("un cerf.".to_string())),
+        ("Été", Some("Rien sur Été".to_string())),
+        ("Mira", Some("une mage; rousse".to_string())),
+        ("Orage", Some("Orage est là.".to_string())),
+        ("Ville", Some("une cité sur la côte".to_string())),
+    ];
+    assert_eq!(entries, expected);
+}
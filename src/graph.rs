@@ -0,0 +1,252 @@
+use crate::document::{IndexedDocument, KeywordIndex};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Write};
+
+/******************************************************************************
+ * Keyword co-occurrence graph.
+ *
+ * Nodes are keywords, and an edge links two keywords appearing in the same context.
+ * Contexts are inlines, text units (paragraph, title, list item) or sections, depending on the scope.
+ * Section contexts only include blocks directly in the section, not sub sections.
+ * Edge weight is the number of shared contexts.
+ * Explicit and implicit occurrences are treated the same.
+ */
+
+/// Granularity of contexts where keywords are considered related.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    Inline,
+    Unit,
+    Section,
+}
+
+impl Scope {
+    pub fn from_name(name: &str) -> Option<Scope> {
+        match name {
+            "inline" => Some(Scope::Inline),
+            "paragraph" => Some(Scope::Unit),
+            "section" => Some(Scope::Section),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Edge {
+    pub weight: usize,
+    /// Index paths of sections where the keywords co-occur.
+    pub sections: BTreeSet<Vec<usize>>,
+}
+
+pub struct CooccurrenceGraph {
+    /// Number of contexts where each keyword occurs, explicitly or implicitly.
+    pub context_counts: Vec<usize>,
+    /// Edges indexed by (a, b) with a < b.
+    pub edges: BTreeMap<(KeywordIndex, KeywordIndex), Edge>,
+}
+
+impl CooccurrenceGraph {
+    pub fn from(document: &IndexedDocument, scope: Scope) -> CooccurrenceGraph {
+        // Invert occurrence tables
+        let nb_keywords = document.keywords().len();
+        let locations = document.inline_locations();
+        let mut keywords_by_inline: Vec<BTreeSet<KeywordIndex>> =
+            vec![BTreeSet::new(); locations.len()];
+        for keyword in 0..nb_keywords {
+            let explicit = document.explicit_occurrences(keyword).iter();
+            let implicit = document.implicit_occurrences(keyword).iter();
+            for inline in explicit.chain(implicit) {
+                keywords_by_inline[*inline].insert(keyword);
+            }
+        }
+        // Contexts of all scopes are contiguous sequences of inlines in document order.
+        let same_context = |a: usize, b: usize| match scope {
+            Scope::Inline => false,
+            Scope::Unit => locations[a].unit == locations[b].unit,
+            Scope::Section => locations[a].section == locations[b].section,
+        };
+        let mut context_counts = vec![0; nb_keywords];
+        let mut edges: BTreeMap<(KeywordIndex, KeywordIndex), Edge> = BTreeMap::new();
+        let mut context_start = 0;
+        while context_start < locations.len() {
+            let mut context_end = context_start + 1;
+            while context_end < locations.len() && same_context(context_start, context_end) {
+                context_end += 1
+            }
+            let keywords: BTreeSet<KeywordIndex> = keywords_by_inline[context_start..context_end]
+                .iter()
+                .flatten()
+                .cloned()
+                .collect();
+            for a in &keywords {
+                context_counts[*a] += 1;
+                for b in keywords.range(a + 1..) {
+                    let edge = edges.entry((*a, *b)).or_default();
+                    edge.weight += 1;
+                    edge.sections
+                        .insert(locations[context_start].section.clone());
+                }
+            }
+            context_start = context_end
+        }
+        CooccurrenceGraph {
+            context_counts,
+            edges,
+        }
+    }
+
+    /// Keywords related to the given one, by decreasing Jaccard similarity of their context sets.
+    /// Similarity is the number of shared contexts over the number of contexts where either occurs.
+    /// Unrelated keywords are not listed.
+    pub fn related_keywords(&self, keyword: KeywordIndex) -> Vec<(KeywordIndex, f64)> {
+        let mut related: Vec<(KeywordIndex, f64)> = self
+            .edges
+            .iter()
+            .filter_map(|((a, b), edge)| {
+                let other = match keyword {
+                    k if k == *a => *b,
+                    k if k == *b => *a,
+                    _ => return None,
+                };
+                let union = self.context_counts[keyword] + self.context_counts[other] - edge.weight;
+                Some((other, edge.weight as f64 / union as f64))
+            })
+            .collect();
+        related.sort_by(|(ka, sa), (kb, sb)| sb.partial_cmp(sa).unwrap().then(ka.cmp(kb)));
+        related
+    }
+}
+
+/// Section provenance of an edge, as a list of breadcrumbs.
+fn edge_provenance(document: &IndexedDocument, edge: &Edge) -> String {
+    let breadcrumbs: Vec<String> = edge
+        .sections
+        .iter()
+        .map(|path| document.section_display_name(path))
+        .collect();
+    breadcrumbs.join("; ")
+}
+
+/// Write the graph in Graphviz DOT format.
+pub fn write_dot<W: Write>(
+    out: &mut W,
+    document: &IndexedDocument,
+    graph: &CooccurrenceGraph,
+) -> fmt::Result {
+    let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+    writeln!(out, "graph keywords {{")?;
+    for (keyword, count) in graph.context_counts.iter().enumerate() {
+        writeln!(
+            out,
+            "\t{} [label={}, contexts={}];",
+            keyword,
+            quote(document.keyword(keyword)),
+            count
+        )?;
+    }
+    for ((a, b), edge) in &graph.edges {
+        writeln!(
+            out,
+            "\t{} -- {} [weight={}, label={}, tooltip={}];",
+            a,
+            b,
+            edge.weight,
+            edge.weight,
+            quote(&edge_provenance(document, edge))
+        )?;
+    }
+    writeln!(out, "}}")
+}
+
+/// Write the graph in GraphML format.
+pub fn write_graphml<W: Write>(
+    out: &mut W,
+    document: &IndexedDocument,
+    graph: &CooccurrenceGraph,
+) -> fmt::Result {
+    let escape = |s: &str| {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    };
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+    )?;
+    writeln!(
+        out,
+        r#"  <key id="label" for="node" attr.name="label" attr.type="string"/>"#
+    )?;
+    writeln!(
+        out,
+        r#"  <key id="contexts" for="node" attr.name="contexts" attr.type="int"/>"#
+    )?;
+    writeln!(
+        out,
+        r#"  <key id="weight" for="edge" attr.name="weight" attr.type="int"/>"#
+    )?;
+    writeln!(
+        out,
+        r#"  <key id="sections" for="edge" attr.name="sections" attr.type="string"/>"#
+    )?;
+    writeln!(out, r#"  <graph id="keywords" edgedefault="undirected">"#)?;
+    for (keyword, count) in graph.context_counts.iter().enumerate() {
+        writeln!(out, r#"    <node id="k{}">"#, keyword)?;
+        writeln!(
+            out,
+            r#"      <data key="label">{}</data>"#,
+            escape(document.keyword(keyword))
+        )?;
+        writeln!(out, r#"      <data key="contexts">{}</data>"#, count)?;
+        writeln!(out, r#"    </node>"#)?;
+    }
+    for ((a, b), edge) in &graph.edges {
+        writeln!(out, r#"    <edge source="k{}" target="k{}">"#, a, b)?;
+        writeln!(out, r#"      <data key="weight">{}</data>"#, edge.weight)?;
+        writeln!(
+            out,
+            r#"      <data key="sections">{}</data>"#,
+            escape(&edge_provenance(document, edge))
+        )?;
+        writeln!(out, r#"    </edge>"#)?;
+    }
+    writeln!(out, r#"  </graph>"#)?;
+    writeln!(out, r#"</graphml>"#)
+}
+
+#[test]
+fn cooccurrences() {
+    let text = "# X #\n*a* *b*\n*c*\n\n*a* and c\n# Y #\n*d*\n\nb\n";
+    let (document, keywords) = crate::ast::parse(text).unwrap();
+    let document = IndexedDocument::from(document, keywords);
+    let [a, b, c, d] = ["a", "b", "c", "d"].map(|k| document.keyword_index(k).unwrap());
+    let weight = |graph: &CooccurrenceGraph, x: usize, y: usize| {
+        graph
+            .edges
+            .get(&(x.min(y), x.max(y)))
+            .map_or(0, |e| e.weight)
+    };
+
+    let graph = CooccurrenceGraph::from(&document, Scope::Inline);
+    assert_eq!(graph.context_counts, vec![2, 2, 2, 1]);
+    assert_eq!(weight(&graph, a, b), 1);
+    assert_eq!(weight(&graph, a, c), 1);
+    assert_eq!(weight(&graph, b, c), 0);
+
+    let graph = CooccurrenceGraph::from(&document, Scope::Unit);
+    assert_eq!(weight(&graph, a, c), 2);
+    assert_eq!(weight(&graph, b, c), 1);
+    assert_eq!(weight(&graph, b, d), 0);
+
+    let graph = CooccurrenceGraph::from(&document, Scope::Section);
+    assert_eq!(weight(&graph, a, c), 1);
+    assert_eq!(weight(&graph, b, d), 1);
+    assert_eq!(
+        graph.edges[&(b, d)].sections,
+        vec![vec![1]].into_iter().collect()
+    );
+    assert_eq!(graph.related_keywords(a), vec![(c, 1.0), (b, 0.5)]);
+    assert_eq!(graph.related_keywords(d), vec![(b, 0.5)]);
+}
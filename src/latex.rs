@@ -0,0 +1,272 @@
+use crate::ast::*;
+use crate::document::{fold_accents, IndexedDocument, KeywordIndex};
+use std::fmt::{self, Write};
+use std::ops::Range;
+
+/******************************************************************************
+ * LaTeX export.
+ *
+ * The document is exported as a complete LaTeX source, using the makeidx package for the index:
+ * - front matter title, author and date are used for \maketitle.
+ * - sections are mapped by level to \section, \subsection, \subsubsection, \paragraph, \subparagraph.
+ * - lists are mapped to itemize and enumerate environments, and rules to a centered \rule.
+ * - block quotes are mapped to the quote environment, and code blocks to the verbatim environment.
+ * - tables are mapped to the tabular environment, with a rule under the header.
+ * - highlights are mapped to \textbf.
+ * - each keyword occurrence has an \index entry for the keyword, at the end of its line.
+ *   Entries of explicit occurrences are primary: their page number is printed in bold.
+ *   In section titles, entries are placed after the sectioning command, to stay out of the table of contents.
+ * Index entries are sorted ignoring case and accents.
+ */
+
+const SECTIONING_COMMANDS: [&str; 5] = [
+    "section",
+    "subsection",
+    "subsubsection",
+    "paragraph",
+    "subparagraph",
+];
+
+pub fn write_document<W: Write>(out: &mut W, document: &IndexedDocument) -> fmt::Result {
+    let front_matter = &document.document().front_matter;
+    writeln!(out, "\\documentclass{{article}}")?;
+    writeln!(out, "\\usepackage[utf8]{{inputenc}}")?;
+    writeln!(out, "\\usepackage[T1]{{fontenc}}")?;
+    writeln!(out, "\\usepackage{{makeidx}}")?;
+    writeln!(out, "\\makeindex")?;
+    if let Some(title) = &front_matter.title {
+        writeln!(out, "\\title{{{}}}", escape(title))?;
+        let author = front_matter.author.as_deref().unwrap_or("");
+        writeln!(out, "\\author{{{}}}", escape(author))?;
+        let date = front_matter.date.as_deref().unwrap_or("");
+        writeln!(out, "\\date{{{}}}", escape(date))?;
+    }
+    writeln!(out, "\\begin{{document}}")?;
+    if front_matter.title.is_some() {
+        writeln!(out, "\\maketitle")?;
+    }
+    writeln!(out)?;
+    write_section_content(out, document, &document.document().content, 0)?;
+    writeln!(out, "\\printindex")?;
+    writeln!(out, "\\end{{document}}")
+}
+
+/// Write blocks and sub sections of a section at the given header level (0 for the document).
+fn write_section_content<W: Write>(
+    out: &mut W,
+    document: &IndexedDocument,
+    content: &SectionContent,
+    level: usize,
+) -> fmt::Result {
+    write_blocks(out, document, &content.blocks)?;
+    for section in &content.sub_sections {
+        let command = SECTIONING_COMMANDS[level.min(SECTIONING_COMMANDS.len() - 1)];
+        let mut entries = Vec::new();
+        let mut title = String::new();
+        write_inline(&mut title, document, &section.title, &mut entries)?;
+        writeln!(out, "\\{}{{{}}}{}\n", command, title, entries.concat())?;
+        write_section_content(out, document, &section.content, level + 1)?
+    }
+    Ok(())
+}
+
+/// Write blocks, each followed by a blank line.
+fn write_blocks<W: Write>(
+    out: &mut W,
+    document: &IndexedDocument,
+    blocks: &[BlockElement],
+) -> fmt::Result {
+    for block in blocks {
+        match block {
+            BlockElement::Paragraph(inlines) => write_inlines(out, document, inlines)?,
+            BlockElement::Rule => {
+                out.write_str("\\begin{center}\\rule{0.5\\linewidth}{0.4pt}\\end{center}\n")?
+            }
+            BlockElement::List(list) => write_list(out, document, list)?,
+            BlockElement::Code { text, .. } => {
+                writeln!(out, "\\begin{{verbatim}}")?;
+                out.write_str(text)?;
+                writeln!(out, "\\end{{verbatim}}")?
+            }
+            BlockElement::Quote(blocks) => {
+                writeln!(out, "\\begin{{quote}}")?;
+                write_blocks(out, document, blocks)?;
+                writeln!(out, "\\end{{quote}}")?
+            }
+            BlockElement::Table(table) => {
+                let columns: String = (table.alignments.iter())
+                    .map(|alignment| match alignment {
+                        Alignment::Center => 'c',
+                        Alignment::Right => 'r',
+                        Alignment::None | Alignment::Left => 'l',
+                    })
+                    .collect();
+                writeln!(out, "\\begin{{tabular}}{{{}}}", columns)?;
+                write_table_row(out, document, &table.header)?;
+                writeln!(out, "\\hline")?;
+                for row in &table.rows {
+                    write_table_row(out, document, row)?
+                }
+                writeln!(out, "\\end{{tabular}}")?
+            }
+        }
+        writeln!(out)?
+    }
+    Ok(())
+}
+
+/// Write cells separated by column separators, with index entries at the end of the last cell:
+/// after the row end, they would start a new empty row.
+fn write_table_row<W: Write>(
+    out: &mut W,
+    document: &IndexedDocument,
+    cells: &[TableCell],
+) -> fmt::Result {
+    let mut entries = Vec::new();
+    for (i, cell) in cells.iter().enumerate() {
+        if i > 0 {
+            out.write_str(" & ")?
+        }
+        for inline in cell {
+            write_inline(out, document, inline, &mut entries)?
+        }
+    }
+    writeln!(out, "{} \\\\", entries.concat())
+}
+
+fn write_list<W: Write>(out: &mut W, document: &IndexedDocument, list: &List) -> fmt::Result {
+    let environment = if list.ordered { "enumerate" } else { "itemize" };
+    writeln!(out, "\\begin{{{}}}", environment)?;
+    for item in &list.items {
+        out.write_str("\\item ")?;
+        write_inlines(out, document, &item.text_content)?;
+        if let Some(sub_list) = &item.sub_list {
+            write_list(out, document, sub_list)?
+        }
+    }
+    writeln!(out, "\\end{{{}}}", environment)
+}
+
+/// Write inlines on separate lines, with index entries next to keyword occurrences.
+fn write_inlines<W: Write>(
+    out: &mut W,
+    document: &IndexedDocument,
+    inlines: &[InlineElement],
+) -> fmt::Result {
+    for inline in inlines {
+        let mut entries = Vec::new();
+        write_inline(out, document, inline, &mut entries)?;
+        writeln!(out, "{}", entries.concat())?
+    }
+    Ok(())
+}
+
+/// Write inline text with highlights.
+/// Index entries of keyword occurrences are added to entries, for the caller to place them after the inline.
+/// An inline is a line of the source, so entries stay on the page of their occurrence except at page breaks.
+fn write_inline<W: Write>(
+    out: &mut W,
+    document: &IndexedDocument,
+    inline: &InlineElement,
+    entries: &mut Vec<String>,
+) -> fmt::Result {
+    let mut highlights: Vec<&Range<usize>> = inline
+        .tags
+        .iter()
+        .filter_map(|(r, tag)| match tag {
+            InlineTag::Highlight => Some(r),
+            _ => None,
+        })
+        .collect();
+    highlights.sort_by_key(|r| r.start);
+    let string = &inline.string;
+    let mut position = 0;
+    for highlight in highlights {
+        out.write_str(&escape(&string[position..highlight.start]))?;
+        write!(out, "\\textbf{{{}}}", escape(&string[highlight.clone()]))?;
+        position = highlight.end
+    }
+    out.write_str(&escape(&string[position..]))?;
+    for (_, tag) in &inline.tags {
+        match tag {
+            InlineTag::ExplicitKeyword(k) => entries.push(index_entry(document, *k, true)),
+            InlineTag::ImplicitKeyword(k) => entries.push(index_entry(document, *k, false)),
+            InlineTag::Highlight | InlineTag::Secret(_) => (),
+        }
+    }
+    Ok(())
+}
+
+/// Index entry for a keyword, with a sort key ignoring case and accents.
+fn index_entry(document: &IndexedDocument, keyword: KeywordIndex, primary: bool) -> String {
+    let name = document.keyword(keyword);
+    // The sort key is not typeset, only characters breaking the entry are removed
+    let (sort_key, _) = fold_accents(&name.to_lowercase());
+    let sort_key: String = sort_key.chars().filter(|c| !"{}\\".contains(*c)).collect();
+    let encap = if primary { "|textbf" } else { "" };
+    format!(
+        "\\index{{{}@{}{}}}",
+        quote_index(&sort_key),
+        quote_index(&escape(name)),
+        encap
+    )
+}
+
+/// Quote makeindex special characters.
+fn quote_index(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len());
+    for c in s.chars() {
+        if "!@|\"".contains(c) {
+            quoted.push('"')
+        }
+        quoted.push(c)
+    }
+    quoted
+}
+
+/// Escape LaTeX special characters.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c)
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[test]
+fn latex_export() {
+    let text =
+        "# Lieux #\nLa **Tour *Noire*** et 50% de la tour noire.\n## Élise & *Éric* ##\n- a\n\n| A | B |\n|---|---|\n| *Mira* | noire |\n";
+    let (document, keywords) = parse(text).unwrap();
+    let document = IndexedDocument::from(document, keywords);
+    let mut out = String::new();
+    write_section_content(&mut out, &document, &document.document().content, 0).unwrap();
+    let expected = r"\section{Lieux}
+
+La \textbf{Tour Noire} et 50\% de la tour noire.\index{noire@Noire|textbf}\index{noire@Noire}
+
+\subsection{Élise \& Éric}\index{eric@Éric|textbf}
+
+\begin{itemize}
+\item a
+\end{itemize}
+
+\begin{tabular}{ll}
+A & B \\
+\hline
+Mira & noire\index{mira@Mira|textbf}\index{noire@Noire} \\
+\end{tabular}
+
+";
+    assert_eq!(out, expected);
+    assert_eq!(quote_index("a!b"), "a\"!b");
+}
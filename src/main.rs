@@ -1,63 +1,700 @@
 /// AST for supported subset of markdown syntax, with parsing.
 mod ast;
 
+/// Parsers for front matter and configuration formats.
+mod metadata;
+
 /// Indexed markdown document data structure.
 mod document;
+
+/// Interpretation of code blocks as data attached to keywords.
+mod code;
 use document::IndexedDocument;
 
-use clap::Arg;
+/// Keyword queries on indexed documents.
+mod query;
+
+/// Keyword co-occurrence graph and its exports.
+mod graph;
+
+/// Html wiki generation.
+mod wiki;
+
+/// Client-side search index for the html wiki.
+mod search;
+
+/// Html templates for the wiki.
+mod template;
+
+/// Markdown serialization of the ast, and markdown wiki generation.
+mod markdown;
+
+/// LaTeX export.
+mod latex;
+
+/// EPUB export of the html wiki.
+mod epub;
+
+/// Alphabetical glossary of keywords.
+mod glossary;
+
+/// Debug dump of the ast.
+mod dump;
+
+/// Removal of secret content.
+mod secret;
+
+/// Build cache for incremental wiki generation.
+mod cache;
+
+/// File change notifications for watch mode.
+#[cfg(target_os = "linux")]
+mod watch;
+
+/// Local preview http server.
+mod serve;
+
+/// Project configuration file.
+mod config;
+use config::{Config, WikiFormat};
+
 use clap::{app_from_crate, crate_authors, crate_description, crate_name, crate_version};
+use clap::{AppSettings, Arg, ArgMatches, SubCommand};
+use std::collections::BTreeMap;
+use std::fs;
 use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Mutex};
+
+/// Command failure, with an exit code for each kind.
+#[derive(Debug, Clone)]
+enum Error {
+    /// Invalid configuration, query or command line. Exit code 1, like clap usage errors.
+    Usage(String),
+    /// Invalid markdown or front matter in inputs. Exit code 2.
+    Parse(String),
+    /// Failure to read inputs or write outputs. Exit code 3.
+    Io(String),
+}
+
+impl Error {
+    fn exit_code(&self) -> i32 {
+        match self {
+            Error::Usage(_) => 1,
+            Error::Parse(_) => 2,
+            Error::Io(_) => 3,
+        }
+    }
+    fn message(&self) -> &str {
+        match self {
+            Error::Usage(m) | Error::Parse(m) | Error::Io(m) => m,
+        }
+    }
+}
+
+fn main() {
+    if let Err(error) = run() {
+        eprintln!("Error: {}", error.message());
+        process::exit(error.exit_code())
+    }
+}
+
+fn run() -> Result<(), Error> {
+    // Arguments shared by subcommands
+    let inputs = Arg::with_name("inputs")
+        .help("Input files concatenated in order, - for standard input [default: configured inputs, or standard input]")
+        .value_name("INPUT")
+        .multiple(true);
+    let output = Arg::with_name("output")
+        .help("Output file, - for standard output")
+        .short("o")
+        .long("output")
+        .value_name("FILE")
+        .default_value("-");
 
-fn main() -> Result<(), String> {
     let args = app_from_crate!()
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .setting(AppSettings::VersionlessSubcommands)
+        .after_help("EXIT CODES:\n    0    Success\n    1    Invalid command line, configuration or query\n    2    Invalid markdown or front matter\n    3    Input or output failure")
         .arg(
-            Arg::with_name("tokens")
-                .help("Prints markdown token list and stop")
-                .long("tokens"),
+            Arg::with_name("config")
+                .help("Configuration file, instead of searching wimd.toml from working directory")
+                .long("config")
+                .value_name("FILE")
+                .global(true),
         )
         .arg(
-            Arg::with_name("keywords")
-                .help("Prints extracted keyword list and stop")
-                .short("k")
-                .long("keywords"),
+            Arg::with_name("lenient")
+                .help("Skip unsupported markdown elements with a warning")
+                .long("lenient")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("strip-secrets")
+                .help("Remove secret content, and keywords only occurring in it")
+                .long("strip-secrets")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("audience")
+                .help("Keep only content visible to this audience, instead of configured audiences [default for commands other than build: the most restricted configured audience]")
+                .long("audience")
+                .value_name("NAME")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("theme")
+                .help("Directory with templates and style.css replacing those of the built-in wiki theme")
+                .long("theme")
+                .value_name("DIR")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("case-folding")
+                .help("Ignore case differences when searching keywords [default: configured, or true]")
+                .long("case-folding")
+                .takes_value(true)
+                .possible_values(&["true", "false"])
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("accent-folding")
+                .help("Ignore accents of latin letters when searching keywords [default: configured, or false]")
+                .long("accent-folding")
+                .takes_value(true)
+                .possible_values(&["true", "false"])
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("stop-list")
+                .help("Keywords or aliases never searched, comma separated, instead of the configured ones")
+                .long("stop-list")
+                .value_name("NAMES")
+                .use_delimiter(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("min-length")
+                .help("Minimum number of characters of searched keywords or aliases [default: configured, or 0]")
+                .long("min-length")
+                .value_name("N")
+                .global(true),
+        )
+        .subcommand(
+            SubCommand::with_name("tokens")
+                .about("Prints markdown token list")
+                .arg(inputs.clone())
+                .arg(output.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("ast")
+                .about("Prints the parsed document tree, with tag ranges underlined")
+                .arg(inputs.clone())
+                .arg(output.clone())
+                .arg(
+                    Arg::with_name("indexed")
+                        .help("Dump after indexing, including implicit keyword occurrences")
+                        .long("indexed"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("keywords")
+                .about("Prints extracted keyword list")
+                .arg(inputs.clone())
+                .arg(output.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Checks that inputs parse, printing warnings")
+                .arg(inputs.clone())
+                .arg(
+                    Arg::with_name("deny-warnings")
+                        .help("Fail with a parse error if there are warnings")
+                        .long("deny-warnings"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("query")
+                .about("Prints text units where keywords occur")
+                .arg(
+                    Arg::with_name("query")
+                        .help("Keywords combined with AND, OR, NOT: \"a AND b NOT c\"")
+                        .required(true),
+                )
+                .arg(inputs.clone())
+                .arg(output.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("graph")
+                .about("Prints the keyword co-occurrence graph")
+                .arg(inputs.clone())
+                .arg(output.clone())
+                .arg(
+                    Arg::with_name("format")
+                        .help("Output format")
+                        .long("format")
+                        .possible_values(&["dot", "graphml"])
+                        .default_value("dot"),
+                )
+                .arg(
+                    Arg::with_name("scope")
+                        .help("Context in which keywords are related")
+                        .long("scope")
+                        .possible_values(&["inline", "paragraph", "section"])
+                        .default_value("paragraph"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("build")
+                .about("Generates the wiki")
+                .arg(inputs.clone())
+                .arg(
+                    Arg::with_name("output")
+                        .help("Output directory [default: configured output]")
+                        .short("o")
+                        .long("output")
+                        .value_name("DIR"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .help("Wiki format [default: configured format]")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["html", "markdown"]),
+                )
+                .arg(
+                    Arg::with_name("force")
+                        .help("Render all pages, ignoring the build cache")
+                        .long("force"),
+                )
+                .arg(
+                    Arg::with_name("watch")
+                        .help("Rebuild when input or configuration files change, until interrupted (Linux only)")
+                        .long("watch"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("latex")
+                .about("Exports the document to LaTeX, with index entries for keyword occurrences")
+                .arg(inputs.clone())
+                .arg(output.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("epub")
+                .about("Exports the html wiki as an EPUB book")
+                .arg(inputs.clone())
+                .arg(
+                    Arg::with_name("output")
+                        .help("Output file")
+                        .short("o")
+                        .long("output")
+                        .value_name("FILE")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("print")
+                .about("Generates a single self-contained html page for printing, with a keyword glossary")
+                .arg(inputs.clone())
+                .arg(output.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("glossary")
+                .about("Prints keywords alphabetically, with their definition or first explicit occurrence")
+                .arg(inputs.clone())
+                .arg(output.clone())
+                .arg(
+                    Arg::with_name("format")
+                        .help("Output format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["markdown", "html"])
+                        .default_value("markdown"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("serve")
+                .about("Serves the html wiki on localhost, reloading pages when inputs change (Linux only)")
+                .arg(inputs)
+                .arg(
+                    Arg::with_name("port")
+                        .help("Port on the loopback interface")
+                        .short("p")
+                        .long("port")
+                        .default_value("8000"),
+                ),
         )
         .get_matches();
 
-    let text = read_stdin()?;
+    let (command, args) = args.subcommand();
+    let args = args.unwrap();
+
+    let config = load_config(command, args)?;
+
+    if command == "build" {
+        if args.is_present("watch") {
+            return watch(command, args, config, |config| build(args, config));
+        }
+        return build(args, &config);
+    }
+
+    if command == "serve" {
+        let port = args.value_of("port").unwrap().parse();
+        let port = port.map_err(|_| Error::Usage("Invalid port".into()))?;
+        let site = Arc::new(Mutex::new(serve::Site::default()));
+        serve::start(Arc::clone(&site), port).map_err(|e| Error::Io(e.to_string()))?;
+        return watch(command, args, config, |config| {
+            match render_site(args, config) {
+                Ok(pages) => site.lock().unwrap().update(pages),
+                Err(error) => {
+                    site.lock().unwrap().set_error(error.message().to_string());
+                    return Err(error);
+                }
+            }
+            eprintln!("Site updated");
+            Ok(())
+        });
+    }
+
+    let sources = read_sources(&input_paths(args, &config)?, &config)?;
 
-    if args.is_present("tokens") {
-        // Test print token stream
-        for event in pulldown_cmark::Parser::new(&text) {
-            println!("{:?}", event)
+    if command == "tokens" {
+        let mut out = String::new();
+        for source in &sources {
+            let options = pulldown_cmark::Options::ENABLE_TABLES;
+            for event in pulldown_cmark::Parser::new_ext(&source.text, options) {
+                out += &format!("{:?}\n", event)
+            }
         }
-        return Ok(());
+        return write_output(args, &out);
     }
 
-    let (ast, keywords) = ast::parse(&text)?;
+    let (ast, keywords, warnings) =
+        ast::parse_sources(&sources, &config.parsing).map_err(Error::Parse)?;
+    for warning in &warnings {
+        eprintln!("warning: {}", warning)
+    }
 
-    if args.is_present("keywords") {
-        let mut keywords: Vec<_> = keywords.into_iter().collect();
-        keywords.sort_unstable();
-        for keyword in keywords {
-            println!("{}", keyword);
+    match command {
+        "ast" => {
+            let mut out = String::new();
+            if args.is_present("indexed") {
+                let document = index_document(ast, keywords, &config);
+                dump::write_document(&mut out, document.document(), document.keywords())
+            } else {
+                dump::write_document(&mut out, &ast, &keywords)
+            }
+            .unwrap();
+            write_output(args, &out)
+        }
+        "keywords" => {
+            let mut keywords: Vec<_> = keywords.into_iter().collect();
+            keywords.sort_unstable();
+            let lines: String = keywords.iter().map(|k| format!("{}\n", k)).collect();
+            write_output(args, &lines)
+        }
+        "check" => match warnings.len() {
+            n if n > 0 && args.is_present("deny-warnings") => {
+                Err(Error::Parse(format!("{} warning(s)", n)))
+            }
+            _ => Ok(()),
+        },
+        "query" => {
+            let document = index_document(ast, keywords, &config);
+            let query = query::Query::parse(args.value_of("query").unwrap());
+            let matches = query.and_then(|query| query::format_matches(&document, &query));
+            write_output(args, &matches.map_err(Error::Usage)?)
+        }
+        "graph" => {
+            let document = index_document(ast, keywords, &config);
+            let scope = graph::Scope::from_name(args.value_of("scope").unwrap()).unwrap();
+            let graph = graph::CooccurrenceGraph::from(&document, scope);
+            let mut out = String::new();
+            match args.value_of("format").unwrap() {
+                "graphml" => graph::write_graphml(&mut out, &document, &graph),
+                _ => graph::write_dot(&mut out, &document, &graph),
+            }
+            .unwrap();
+            write_output(args, &out)
         }
-        return Ok(());
+        "latex" => {
+            let document = index_document(ast, keywords, &config);
+            let mut out = String::new();
+            latex::write_document(&mut out, &document).unwrap();
+            write_output(args, &out)
+        }
+        "epub" => {
+            let document = index_document(ast, keywords, &config);
+            let theme = wiki::Theme::load(&config).map_err(Error::Usage)?;
+            let path = args.value_of("output").unwrap();
+            let archive = epub::epub(&document, &config, &theme);
+            fs::write(path, archive).map_err(|e| Error::Io(format!("{}: {}", path, e)))
+        }
+        "print" => {
+            let document = index_document(ast, keywords, &config);
+            let theme = wiki::Theme::load(&config).map_err(Error::Usage)?;
+            write_output(args, &wiki::render_single_page(&document, &config, &theme))
+        }
+        "glossary" => {
+            let document = index_document(ast, keywords, &config);
+            let format = args.value_of("format").unwrap();
+            let format = glossary::GlossaryFormat::from_name(format).unwrap();
+            let mut out = String::new();
+            glossary::write_glossary(&mut out, &document, format).unwrap();
+            write_output(args, &out)
+        }
+        _ => unreachable!(),
     }
+}
 
-    let document = IndexedDocument::from(ast, keywords);
+/// Load configuration, with overrides from command line.
+fn load_config(command: &str, args: &ArgMatches) -> Result<Config, Error> {
+    let mut config = match args.value_of("config") {
+        Some(path) => Config::load(Path::new(path)),
+        None => Config::discover(),
+    }
+    .map_err(Error::Usage)?;
+    if args.is_present("lenient") {
+        config.parsing.lenient = true
+    }
+    if let Some(theme) = args.value_of("theme") {
+        config.theme = Some(PathBuf::from(theme))
+    }
+    if let Some(value) = args.value_of("case-folding") {
+        config.matching.case_folding = value == "true"
+    }
+    if let Some(value) = args.value_of("accent-folding") {
+        config.matching.accent_folding = value == "true"
+    }
+    if let Some(names) = args.values_of("stop-list") {
+        config.matching.stop_list = names.map(String::from).collect()
+    }
+    if let Some(value) = args.value_of("min-length") {
+        config.matching.min_length = value
+            .parse()
+            .map_err(|_| Error::Usage(format!("Invalid --min-length: {}", value)))?
+    }
+    let audience = match args.value_of("audience") {
+        Some(audience) => Some(audience),
+        None if args.is_present("strip-secrets") => Some(secret::PUBLIC),
+        None => None,
+    };
+    if let Some(audience) = audience {
+        config.parsing.audience = Some(audience.to_string());
+        config.audiences.clear()
+    }
+    // Commands with a single output never show more than any configured audience may see
+    if command != "build" {
+        if let Some(audience) = secret::most_restricted(&config.audiences) {
+            config.parsing.audience = Some(audience.clone())
+        }
+    }
+    if let ("build", Some(output)) = (command, args.value_of("output")) {
+        config.output = PathBuf::from(output)
+    }
+    if let ("build", Some(format)) = (command, args.value_of("format")) {
+        config.format = WikiFormat::from_name(format).unwrap()
+    }
+    Ok(config)
+}
+
+/// Build the wiki, or one wiki for each configured audience.
+fn build(args: &ArgMatches, config: &Config) -> Result<(), Error> {
+    let sources = read_sources(&input_paths(args, config)?, config)?;
+    if config.audiences.is_empty() {
+        return build_wiki(args, config, &sources);
+    }
+    for audience in &config.audiences {
+        let mut audience_config = config.clone();
+        audience_config.output = config.output.join(document::slugify(audience));
+        audience_config.parsing.audience = Some(audience.clone());
+        build_wiki(args, &audience_config, &sources)?
+    }
+    Ok(())
+}
 
+/// Build one wiki, rendering only pages changed since the previous build if possible.
+fn build_wiki(args: &ArgMatches, config: &Config, sources: &[ast::Source]) -> Result<(), Error> {
+    let cache_path = config.output.join(cache::CACHE_FILE_NAME);
+    let previous_build = if args.is_present("force") {
+        None
+    } else {
+        cache::BuildCache::load(&cache_path)
+    };
+    if let Some(previous_build) = &previous_build {
+        if previous_build.is_up_to_date(sources, config) {
+            eprintln!("{}: up to date", config.output.display());
+            return Ok(());
+        }
+    }
+    let (ast, keywords, warnings) =
+        ast::parse_sources(sources, &config.parsing).map_err(Error::Parse)?;
+    for warning in &warnings {
+        eprintln!("warning: {}", warning)
+    }
+    let document = index_document(ast, keywords, config);
+    let build = cache::BuildCache::new(sources, config, &document);
+    let io_error = |e: io::Error| Error::Io(format!("{}: {}", config.output.display(), e));
+    match config.format {
+        WikiFormat::Html => {
+            let theme = wiki::Theme::load(config).map_err(Error::Usage)?;
+            let changed_keywords =
+                previous_build.and_then(|previous| previous.changed_keywords(&build));
+            let changed_keywords = changed_keywords.as_ref();
+            wiki::generate(&document, config, &theme, changed_keywords).map_err(io_error)?
+        }
+        WikiFormat::Markdown => markdown::generate_wiki(&document, config).map_err(io_error)?,
+    }
+    build.save(&cache_path).map_err(io_error)?;
+    eprintln!("{}: built", config.output.display());
     Ok(())
 }
 
-fn read_stdin() -> Result<String, String> {
+/// Index the document with configured matching options and code interpreters.
+fn index_document(
+    ast: ast::Document,
+    keywords: ast::KeywordSet,
+    config: &Config,
+) -> IndexedDocument {
+    IndexedDocument::with_options(ast, keywords, &config.matching, &config.code_interpreters())
+}
+
+/// Parse inputs and render all wiki pages in memory.
+fn render_site(args: &ArgMatches, config: &Config) -> Result<BTreeMap<String, String>, Error> {
+    let sources = read_sources(&input_paths(args, config)?, config)?;
+    let (ast, keywords, warnings) =
+        ast::parse_sources(&sources, &config.parsing).map_err(Error::Parse)?;
+    for warning in &warnings {
+        eprintln!("warning: {}", warning)
+    }
+    let document = index_document(ast, keywords, config);
+    let theme = wiki::Theme::load(config).map_err(Error::Usage)?;
+    wiki::render_pages(&document, config, &theme).map_err(|e| Error::Io(e.to_string()))
+}
+
+/// Run the action, then again each time input or configuration files change.
+/// Action errors are printed without stopping; only failures to watch files are returned.
+#[cfg(target_os = "linux")]
+fn watch<F>(command: &str, args: &ArgMatches, config: Config, mut action: F) -> Result<(), Error>
+where
+    F: FnMut(&Config) -> Result<(), Error>,
+{
+    let config_file = match args.value_of("config") {
+        Some(path) => Some(PathBuf::from(path)),
+        None => Config::find(),
+    };
+    let watched_inputs = |config: &Config| -> Result<Vec<PathBuf>, Error> {
+        let paths = input_paths(args, config)?;
+        if paths.iter().any(|path| path == Path::new("-")) {
+            return Err(Error::Usage("Watching requires input files".into()));
+        }
+        Ok(paths.iter().map(|path| watch::normalize(path)).collect())
+    };
+    let watched_files = |inputs: &[PathBuf]| -> Vec<PathBuf> {
+        let config_file = config_file.iter().map(|path| watch::normalize(path));
+        inputs.iter().cloned().chain(config_file).collect()
+    };
+    let mut inputs = watched_inputs(&config)?;
+    let mut watched = watched_files(&inputs);
+    // Watch before the first action, so that changes during an action trigger the next one
+    let io_error = |e: io::Error| Error::Io(format!("Watching input files: {}", e));
+    let mut watcher = watch::FileWatcher::new(&watched).map_err(io_error)?;
+    let mut config: Result<Config, Error> = Ok(config);
+    loop {
+        let result = match &config {
+            Ok(config) => action(config),
+            Err(error) => Err(error.clone()),
+        };
+        if let Err(error) = result {
+            eprintln!("Error: {}", error.message())
+        }
+        // Wait for a change of a watched file, or of the list of input files
+        loop {
+            let changed = watcher.wait().map_err(io_error)?;
+            config = load_config(command, args);
+            let new_inputs = config.as_ref().ok().map(&watched_inputs);
+            let inputs_changed = match new_inputs {
+                Some(Ok(new_inputs)) if new_inputs != inputs => {
+                    inputs = new_inputs;
+                    true
+                }
+                _ => false,
+            };
+            let watched_changed = changed.iter().any(|path| watched.contains(path));
+            if inputs_changed {
+                watched = watched_files(&inputs);
+                watcher.watch(&watched).map_err(io_error)?;
+            }
+            if inputs_changed || watched_changed {
+                break;
+            }
+        }
+    }
+}
+
+/// File change notifications are only implemented with inotify.
+#[cfg(not(target_os = "linux"))]
+fn watch<F>(_: &str, _: &ArgMatches, _: Config, _: F) -> Result<(), Error>
+where
+    F: FnMut(&Config) -> Result<(), Error>,
+{
+    Err(Error::Usage(
+        "Watching input files is only supported on Linux".into(),
+    ))
+}
+
+/// Input files from the command line, or configured input files, or standard input as "-".
+fn input_paths(args: &ArgMatches, config: &Config) -> Result<Vec<PathBuf>, Error> {
+    match args.values_of("inputs") {
+        Some(inputs) => Ok(inputs.map(PathBuf::from).collect()),
+        None if config.inputs.is_empty() => Ok(vec![PathBuf::from("-")]),
+        None => config.input_files().map_err(Error::Io),
+    }
+}
+
+/// Read input files, with "-" for standard input.
+fn read_sources(paths: &[PathBuf], config: &Config) -> Result<Vec<ast::Source>, Error> {
+    paths
+        .iter()
+        .map(|path| {
+            if path == Path::new("-") {
+                return Ok(ast::Source {
+                    name: "<stdin>".into(),
+                    text: read_stdin()?,
+                });
+            }
+            let text = fs::read_to_string(path)
+                .map_err(|e| Error::Io(format!("{}: {}", path.display(), e)))?;
+            // Name files relative to the project root if possible, for shorter messages
+            let name = path.strip_prefix(&config.root).unwrap_or(path);
+            Ok(ast::Source {
+                name: name.display().to_string(),
+                text,
+            })
+        })
+        .collect()
+}
+
+fn read_stdin() -> Result<String, Error> {
     let mut s = String::new();
     io::stdin()
         .read_to_string(&mut s)
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| Error::Io(format!("<stdin>: {}", e)))?;
     Ok(s)
 }
 
+/// Write text to the output file argument, or standard output for "-".
+fn write_output(args: &ArgMatches, text: &str) -> Result<(), Error> {
+    match args.value_of("output").unwrap() {
+        "-" => {
+            print!("{}", text);
+            Ok(())
+        }
+        path => fs::write(path, text).map_err(|e| Error::Io(format!("{}: {}", path, e))),
+    }
+}
+
 // Description / Associate many things with keywords:
 // Sentence version "<kwd> : text ; text ; text."
 // List version "<kwd>:\n- <text>\n- <text>"
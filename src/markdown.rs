@@ -0,0 +1,578 @@
+use crate::ast::*;
+use crate::config::Config;
+use crate::document::{for_each_text_unit, slugify, IndexedDocument, KeywordIndex, TextUnitKind};
+use crate::graph::{CooccurrenceGraph, Scope};
+use crate::wiki::MAX_RELATED_KEYWORDS;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::{self, Write};
+use std::fs;
+use std::io;
+use std::ops::Range;
+
+/******************************************************************************
+ * Markdown output.
+ *
+ * The ast serializer writes CommonMark that parses back to the same ast.
+ * Text is escaped conservatively, and blocks are separated by blank lines.
+ * Keyword occurrences are written by a formatting function, to produce emphasis or links.
+ * Highlights, secret ranges and explicit keyword occurrences come from nested markdown tags.
+ * Implicit occurrences are found in text regardless of markup, so they may partly overlap highlights:
+ * highlights and secret ranges are split at their limits, and everything is written as nested markup.
+ * Secret ranges are written between "||" delimiters, except for entirely secret text units
+ * which are written with a secret marker, as they may contain more restricted ranges.
+ *
+ * The markdown wiki is a directory of markdown files, for use in other wiki engines:
+ * - index.md: the whole document, with keyword occurrences linked to keyword pages.
+ * - <keyword slug>.md: for each keyword, related keywords, referencing sections and text units where it occurs.
+ * Links are relative, and link to sections of index.md with section slugs as anchors.
+ * Wiki engines compute heading anchors with their own rules, so index.md has explicit html anchors before headings.
+ * Keyword file names are slugs of keyword names, with a numeric suffix if already used.
+ */
+
+/// Markdown of a keyword occurrence, from keyword, explicitness, and markdown of the occurrence text.
+pub type KeywordFormat<'a> = &'a dyn Fn(KeywordIndex, bool, &str) -> String;
+
+/// Keyword occurrences as in the original markdown: explicit ones emphasized, implicit ones as text.
+#[cfg(test)]
+fn keyword_emphasis(_: KeywordIndex, explicit: bool, text: &str) -> String {
+    if explicit {
+        format!("*{}*", text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Anchor written before the header of a section, from its path.
+type SectionAnchor<'a> = &'a dyn Fn(&[usize]) -> Option<String>;
+
+/// Write blocks and sub sections of a section at the given header level (0 for the document).
+/// Each block or title is followed by a blank line.
+#[cfg(test)]
+fn write_section_content<W: Write>(
+    out: &mut W,
+    content: &SectionContent,
+    level: usize,
+    keyword: KeywordFormat,
+) -> fmt::Result {
+    write_sections(out, content, level, keyword, &|_| None, &mut Vec::new())
+}
+
+/// Write section content, with an html anchor line before headers of sections which have one.
+fn write_sections<W: Write>(
+    out: &mut W,
+    content: &SectionContent,
+    level: usize,
+    keyword: KeywordFormat,
+    anchor: SectionAnchor,
+    path: &mut Vec<usize>,
+) -> fmt::Result {
+    write_blocks(out, &content.blocks, keyword)?;
+    for (i, section) in content.sub_sections.iter().enumerate() {
+        path.push(i);
+        if let Some(anchor) = anchor(path) {
+            writeln!(out, "<a id=\"{}\"></a>", anchor)?
+        }
+        write!(out, "{} ", "#".repeat(level + 1))?;
+        let title = std::slice::from_ref(&section.title);
+        let secret = unit_secret(title);
+        write_inline(out, &section.title, secret.is_some(), keyword)?;
+        if let Some(audiences) = secret {
+            write!(out, " {}", format_secret_marker(audiences))?
+        }
+        out.write_str("\n\n")?;
+        write_sections(out, &section.content, level + 1, keyword, anchor, path)?;
+        path.pop();
+    }
+    Ok(())
+}
+
+/// Write blocks, each followed by a blank line.
+/// Quotes are written with their lines prefixed by quote markers.
+fn write_blocks<W: Write>(
+    out: &mut W,
+    blocks: &[BlockElement],
+    keyword: KeywordFormat,
+) -> fmt::Result {
+    for block in blocks {
+        match block {
+            BlockElement::Paragraph(inlines) => write_inlines(out, inlines, "", keyword)?,
+            BlockElement::Rule => out.write_str("***")?,
+            BlockElement::List(list) => write_list(out, list, "", keyword)?,
+            BlockElement::Code {
+                lang, text, secret, ..
+            } => {
+                // The fence must be longer than any backtick sequence of the text
+                let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+                let fence = "`".repeat(longest.max(2) + 1);
+                let mut info = lang.clone().unwrap_or_default();
+                if let Some(audiences) = secret {
+                    info = format!("{} {}", info, format_secret_marker(audiences))
+                }
+                writeln!(out, "{}{}", fence, info.trim_start())?;
+                out.write_str(text)?;
+                out.write_str(&fence)?
+            }
+            BlockElement::Quote(blocks) => {
+                let mut quote = String::new();
+                write_blocks(&mut quote, blocks, keyword)?;
+                for (i, line) in quote.trim_end().lines().enumerate() {
+                    if i > 0 {
+                        out.write_char('\n')?
+                    }
+                    match line.is_empty() {
+                        true => out.write_char('>')?,
+                        false => write!(out, "> {}", line)?,
+                    }
+                }
+            }
+            BlockElement::Table(table) => {
+                write_table_row(out, &table.header, keyword)?;
+                out.write_str("\n|")?;
+                for alignment in &table.alignments {
+                    let delimiter = match alignment {
+                        Alignment::None => "---",
+                        Alignment::Left => ":---",
+                        Alignment::Center => ":---:",
+                        Alignment::Right => "---:",
+                    };
+                    write!(out, " {} |", delimiter)?
+                }
+                for row in &table.rows {
+                    out.write_char('\n')?;
+                    write_table_row(out, row, keyword)?
+                }
+            }
+        }
+        out.write_str("\n\n")?
+    }
+    Ok(())
+}
+
+fn write_table_row<W: Write>(
+    out: &mut W,
+    cells: &[TableCell],
+    keyword: KeywordFormat,
+) -> fmt::Result {
+    out.write_char('|')?;
+    for cell in cells {
+        out.write_char(' ')?;
+        write_inlines(out, cell, "", keyword)?;
+        out.write_str(" |")?
+    }
+    Ok(())
+}
+
+/// Write list items, with lines after the first one prefixed by the indentation.
+fn write_list<W: Write>(
+    out: &mut W,
+    list: &List,
+    indent: &str,
+    keyword: KeywordFormat,
+) -> fmt::Result {
+    for (i, item) in list.items.iter().enumerate() {
+        if i > 0 {
+            write!(out, "\n{}", indent)?
+        }
+        let marker = if list.ordered {
+            format!("{}. ", i + 1)
+        } else {
+            "- ".to_string()
+        };
+        out.write_str(&marker)?;
+        let item_indent = format!("{}{}", indent, " ".repeat(marker.len()));
+        write_inlines(out, &item.text_content, &item_indent, keyword)?;
+        if let Some(sub_list) = &item.sub_list {
+            write!(out, "\n{}", item_indent)?;
+            write_list(out, sub_list, &item_indent, keyword)?
+        }
+    }
+    Ok(())
+}
+
+/// Write inlines on separate lines, with lines after the first one prefixed by the indentation.
+pub fn write_inlines<W: Write>(
+    out: &mut W,
+    inlines: &[InlineElement],
+    indent: &str,
+    keyword: KeywordFormat,
+) -> fmt::Result {
+    let secret = unit_secret(inlines);
+    if let Some(audiences) = secret {
+        write!(out, "{} ", format_secret_marker(audiences))?
+    }
+    for (i, inline) in inlines.iter().enumerate() {
+        if i > 0 {
+            write!(out, "\n{}", indent)?
+        }
+        write_inline(out, inline, secret.is_some(), keyword)?
+    }
+    Ok(())
+}
+
+/// Write inline text with keyword occurrences and highlighting.
+/// In a secret unit, the tag making the whole inline secret is left to the unit secret marker.
+pub fn write_inline<W: Write>(
+    out: &mut W,
+    inline: &InlineElement,
+    secret_unit: bool,
+    keyword: KeywordFormat,
+) -> fmt::Result {
+    let mut tags: Vec<(Range<usize>, &InlineTag)> = (inline.tags.iter())
+        .map(|(range, tag)| (range.clone(), tag))
+        .collect();
+    if secret_unit {
+        let whole = whole_secret(inline);
+        let position = tags.iter().position(|(range, tag)| match tag {
+            InlineTag::Secret(audiences) => Some(audiences) == whole && range.start == 0,
+            _ => false,
+        });
+        tags.remove(position.unwrap());
+    }
+    let string = &inline.string;
+    let mut tags = split_crossing_tags(string, tags);
+    tags.sort_by_key(|(range, _)| (range.start, std::cmp::Reverse(range.end)));
+    write_tagged(out, string, 0..string.len(), &tags, keyword)
+}
+
+/// Split highlights and secret ranges at the limits of keyword occurrences crossing them, so that tags nest.
+/// Highlight pieces are trimmed, as strong delimiters cannot be next to inner spaces.
+fn split_crossing_tags<'t>(
+    string: &str,
+    tags: Vec<(Range<usize>, &'t InlineTag)>,
+) -> Vec<(Range<usize>, &'t InlineTag)> {
+    let is_keyword = |tag: &InlineTag| match tag {
+        InlineTag::ExplicitKeyword(_) | InlineTag::ImplicitKeyword(_) => true,
+        InlineTag::Highlight | InlineTag::Secret(_) => false,
+    };
+    let keywords: Vec<Range<usize>> = (tags.iter())
+        .filter(|(_, tag)| is_keyword(tag))
+        .map(|(range, _)| range.clone())
+        .collect();
+    let mut split = Vec::new();
+    for (range, tag) in tags {
+        if is_keyword(tag) {
+            split.push((range, tag));
+            continue;
+        }
+        let mut cuts: Vec<usize> = (keywords.iter())
+            .filter(|k| !(range.start <= k.start && k.end <= range.end))
+            .flat_map(|k| vec![k.start, k.end])
+            .filter(|cut| range.start < *cut && *cut < range.end)
+            .collect();
+        cuts.sort_unstable();
+        let mut start = range.start;
+        for end in cuts.into_iter().chain(std::iter::once(range.end)) {
+            let (mut piece_start, mut piece_end) = (start, end);
+            if let InlineTag::Highlight = tag {
+                piece_start = end - string[start..end].trim_start().len();
+                piece_end = start + string[start..end].trim_end().len();
+            }
+            if piece_start < piece_end {
+                split.push((piece_start..piece_end, tag))
+            }
+            start = end
+        }
+    }
+    split
+}
+
+/// Write a range of text with the tags in it, sorted by start and outermost first.
+/// Tags must be properly nested, and are written as nested markup.
+fn write_tagged<W: Write>(
+    out: &mut W,
+    string: &str,
+    range: Range<usize>,
+    tags: &[(Range<usize>, &InlineTag)],
+    keyword: KeywordFormat,
+) -> fmt::Result {
+    let mut position = range.start;
+    let mut i = 0;
+    while i < tags.len() {
+        let (tag_range, tag) = &tags[i];
+        let nested_end = tags[i + 1..]
+            .iter()
+            .position(|(r, _)| r.start >= tag_range.end)
+            .map_or(tags.len(), |n| i + 1 + n);
+        out.write_str(&escape(string, position..tag_range.start))?;
+        let mut inner = String::new();
+        let nested = &tags[i + 1..nested_end];
+        write_tagged(&mut inner, string, tag_range.clone(), nested, keyword)?;
+        match tag {
+            InlineTag::Highlight => write!(out, "**{}**", inner)?,
+            InlineTag::Secret(audiences) if audiences.is_empty() => write!(out, "||{}||", inner)?,
+            InlineTag::Secret(audiences) => {
+                write!(out, "||{} {}||", format_secret_marker(audiences), inner)?
+            }
+            InlineTag::ExplicitKeyword(k) => out.write_str(&keyword(*k, true, &inner))?,
+            InlineTag::ImplicitKeyword(k) => out.write_str(&keyword(*k, false, &inner))?,
+        }
+        position = tag_range.end;
+        i = nested_end
+    }
+    out.write_str(&escape(string, position..range.end))
+}
+
+/// Escape markdown punctuation in a range of a line.
+/// At the start of the line, list markers are also escaped.
+pub fn escape(line: &str, range: Range<usize>) -> String {
+    let mut marker = None;
+    if range.start == 0 {
+        let digits = line
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(line.len());
+        marker = match line[digits..].chars().next() {
+            Some('-') | Some('+') if digits == 0 => Some(0),
+            Some('.') | Some(')') if digits > 0 => Some(digits),
+            _ => None,
+        }
+    }
+    let mut escaped = String::with_capacity(range.len());
+    for (offset, c) in line[range.clone()].char_indices() {
+        if "\\`*_[]<>#&|~".contains(c) || marker == Some(range.start + offset) {
+            escaped.push('\\')
+        }
+        escaped.push(c)
+    }
+    escaped
+}
+
+/// Generate the markdown wiki in the configured output directory, creating it if needed.
+pub fn generate_wiki(document: &IndexedDocument, config: &Config) -> io::Result<()> {
+    let output_dir = config.output.as_path();
+    fs::create_dir_all(output_dir)?;
+    for (name, page) in render_wiki(document) {
+        fs::write(output_dir.join(name), page)?
+    }
+    Ok(())
+}
+
+/// Render all markdown wiki pages in memory, by file name.
+fn render_wiki(document: &IndexedDocument) -> BTreeMap<String, String> {
+    let page_names = keyword_page_names(document);
+    let link = |keyword: KeywordIndex, explicit: bool, text: &str| {
+        let page = &page_names[keyword];
+        if explicit {
+            format!("[*{}*]({})", text, page)
+        } else {
+            format!("[{}]({})", text, page)
+        }
+    };
+    let mut pages = BTreeMap::new();
+    let mut index = String::new();
+    let anchor = |path: &[usize]| document.section_slug(path).map(str::to_string);
+    let content = &document.document().content;
+    write_sections(&mut index, content, 0, &link, &anchor, &mut Vec::new()).unwrap();
+    pages.insert("index.md".to_string(), finish(index));
+
+    let graph = CooccurrenceGraph::from(document, Scope::Section);
+    for keyword in 0..document.keywords().len() {
+        let page = keyword_page(document, &graph, &page_names, &link, keyword);
+        pages.insert(page_names[keyword].clone(), finish(page));
+    }
+    pages
+}
+
+/// Remove the trailing blank line of a page.
+fn finish(mut page: String) -> String {
+    page.truncate(page.trim_end().len());
+    page.push('\n');
+    page
+}
+
+/// File names of keyword pages, by keyword index.
+fn keyword_page_names(document: &IndexedDocument) -> Vec<String> {
+    let mut used: HashSet<String> = HashSet::new();
+    used.insert("index".to_string());
+    (0..document.keywords().len())
+        .map(|keyword| {
+            let base = match slugify(document.keyword(keyword)) {
+                slug if slug.is_empty() => "keyword".to_string(),
+                slug => slug,
+            };
+            let mut name = base.clone();
+            let mut n = 1;
+            while used.contains(&name) {
+                n += 1;
+                name = format!("{}-{}", base, n)
+            }
+            used.insert(name.clone());
+            format!("{}.md", name)
+        })
+        .collect()
+}
+
+/// Link to a section of the document page.
+fn section_link(document: &IndexedDocument, path: &[usize]) -> String {
+    let page = match document.section_slug(path) {
+        Some(anchor) => format!("index.md#{}", anchor),
+        None => "index.md".to_string(),
+    };
+    let name = document.section_display_name(path);
+    format!("[{}]({})", escape(&name, 0..name.len()), page)
+}
+
+/// Page for one keyword: related keywords, and text units where it occurs grouped by section.
+fn keyword_page(
+    document: &IndexedDocument,
+    graph: &CooccurrenceGraph,
+    page_names: &[String],
+    link: KeywordFormat,
+    keyword: KeywordIndex,
+) -> String {
+    let mut page = String::new();
+    let out = &mut page;
+    let name = document.keyword(keyword);
+    writeln!(out, "# {}\n", escape(name, 0..name.len())).unwrap();
+    if let Some(category) = document.keyword_category(keyword) {
+        let category = document.category(category);
+        writeln!(out, "Category: {}\n", escape(category, 0..category.len())).unwrap();
+    }
+    for data in document.keyword_data(keyword) {
+        for (key, value) in &data.fields {
+            let (key, value) = (escape(key, 0..key.len()), escape(value, 0..value.len()));
+            writeln!(out, "- **{}**: {}", key, value).unwrap();
+        }
+        writeln!(out).unwrap();
+    }
+
+    let related = graph.related_keywords(keyword);
+    if !related.is_empty() {
+        writeln!(out, "## Related keywords\n").unwrap();
+        for (other, _) in related.into_iter().take(MAX_RELATED_KEYWORDS) {
+            let name = document.keyword(other);
+            let name = escape(name, 0..name.len());
+            writeln!(out, "- [{}]({})", name, page_names[other]).unwrap();
+        }
+        writeln!(out).unwrap();
+    }
+
+    writeln!(out, "## Referenced in\n").unwrap();
+    for path in document.referencing_sections(keyword) {
+        writeln!(out, "- {}", section_link(document, &path)).unwrap();
+    }
+    writeln!(out).unwrap();
+
+    let explicit = document.explicit_occurrences(keyword).iter();
+    let implicit = document.implicit_occurrences(keyword).iter();
+    let units: BTreeSet<usize> = explicit
+        .chain(implicit)
+        .map(|inline| document.inline_location(*inline).unit)
+        .collect();
+    writeln!(out, "## Occurrences\n").unwrap();
+    let mut unit = 0;
+    let mut current_section: Option<Vec<usize>> = None;
+    for_each_text_unit(document.document(), &mut |path, kind, inlines| {
+        if units.contains(&unit) {
+            if current_section.as_deref() != Some(path) {
+                writeln!(out, "### {}\n", section_link(document, path)).unwrap();
+                current_section = Some(path.to_vec())
+            }
+            match kind {
+                TextUnitKind::Title(_) => {
+                    out.write_str("**").unwrap();
+                    write_inlines(out, inlines, "", link).unwrap();
+                    out.write_str("**").unwrap()
+                }
+                TextUnitKind::Paragraph | TextUnitKind::TableCell { .. } => {
+                    write_inlines(out, inlines, "", link).unwrap()
+                }
+                TextUnitKind::ListItem { .. } => {
+                    out.write_str("- ").unwrap();
+                    write_inlines(out, inlines, "  ", link).unwrap()
+                }
+            }
+            out.write_str("\n\n").unwrap()
+        }
+        unit += 1
+    });
+    page
+}
+
+#[test]
+fn round_trip() {
+    let text = "\
+Top *a* and a **b *c* d**.
+1\\. not a list
+\\- not a list \\# & more
+
+***
+
+> Read *a* aloud.
+>
+> - item
+
+# T *a* #
+
+- x
+  - y
+    z
+- 2\\. item
+";
+    let (document, keywords) = parse(text).unwrap();
+    let mut out = String::new();
+    let content = &document.content;
+    write_section_content(&mut out, content, 0, &keyword_emphasis).unwrap();
+    let expected = "\
+Top *a* and a **b *c* d**.
+1\\. not a list
+\\- not a list \\# \\& more
+
+***
+
+> Read *a* aloud.
+>
+> - item
+
+# T *a*
+
+- x
+  - y
+    z
+- 2\\. item
+
+";
+    assert_eq!(out, expected);
+    let (reparsed, reparsed_keywords) = parse(&out).unwrap();
+    let mut again = String::new();
+    write_section_content(&mut again, &reparsed.content, 0, &keyword_emphasis).unwrap();
+    assert_eq!(again, out);
+    assert_eq!(reparsed_keywords.len(), keywords.len());
+
+    let document = IndexedDocument::from(document, keywords);
+    let pages = render_wiki(&document);
+    let names: Vec<&String> = pages.keys().collect();
+    assert_eq!(names, vec!["a.md", "c.md", "index.md"]);
+    assert!(pages["index.md"].starts_with("Top [*a*](a.md) and [a](a.md) **b [*c*](c.md) d**"));
+    assert!(pages["c.md"].contains("## Referenced in\n\n- [(top)](index.md)\n"));
+
+    // Implicit occurrence partly overlapping a highlight
+    let (document, keywords) = parse("*Tour Noire*\n\n**la tour** noire\n").unwrap();
+    let document = IndexedDocument::from(document, keywords);
+    let pages = render_wiki(&document);
+    assert_eq!(
+        pages["index.md"],
+        "[*Tour Noire*](tour-noire.md)\n\n**la** [**tour** noire](tour-noire.md)\n"
+    );
+
+    // Explicit anchors for accented and duplicated headers
+    let text = "# Général #\n*Mira*\n\n# Général #\nMira\n";
+    let (document, keywords) = parse(text).unwrap();
+    let document = IndexedDocument::from(document, keywords);
+    let pages = render_wiki(&document);
+    assert_eq!(
+        pages["index.md"],
+        "\
+<a id=\"general\"></a>
+# Général
+
+[*Mira*](mira.md)
+
+<a id=\"general-2\"></a>
+# Général
+
+[Mira](mira.md)
+"
+    );
+    assert!(pages["mira.md"]
+        .contains("- [Général](index.md#general)\n- [Général](index.md#general-2)\n"));
+}
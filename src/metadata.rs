@@ -0,0 +1,397 @@
+/******************************************************************************
+ * Minimal parsers for the subsets of YAML and TOML used in front matter and configuration.
+ *
+ * Both produce a table of values, in order of appearance.
+ * Values are untyped: numbers, dates and booleans are kept as strings.
+ *
+ * YAML subset:
+ * - "key: value" with plain, 'single' or "double" quoted scalar values.
+ * - "key: [a, b]" inline lists.
+ * - "key:" followed by indented "- item" lines (block list) or "sub_key: value" lines (table).
+ *   In a table, "sub_key:" may itself be followed by a more indented block list or table.
+ * TOML subset:
+ * - "key = value" with quoted strings, bare values and inline lists "[a, b]".
+ * - "[table]" and "[table.sub_table]" headers, grouping following keys.
+ * Lines starting with '#' are comments in both, as well as the end of lines from an unquoted '#'
+ * (after a space in YAML).
+ */
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Scalar(String),
+    List(Vec<String>),
+    Table(Table),
+}
+
+pub type Table = Vec<(String, Value)>;
+
+/// Error message and line number (starting from 0) in the parsed text.
+pub type Error = (String, usize);
+
+impl Value {
+    pub fn as_scalar(&self) -> Option<&str> {
+        match self {
+            Value::Scalar(s) => Some(s),
+            _ => None,
+        }
+    }
+    /// List, or scalar as a single element list.
+    pub fn as_list(&self) -> Option<Vec<String>> {
+        match self {
+            Value::Scalar(s) => Some(vec![s.clone()]),
+            Value::List(l) => Some(l.clone()),
+            Value::Table(_) => None,
+        }
+    }
+    pub fn as_table(&self) -> Option<&Table> {
+        match self {
+            Value::Table(t) => Some(t),
+            _ => None,
+        }
+    }
+}
+
+/// Find the value of a key in a table.
+pub fn get<'t>(table: &'t Table, key: &str) -> Option<&'t Value> {
+    table.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+}
+
+pub fn parse_yaml(text: &str) -> Result<Table, Error> {
+    let mut table = Table::new();
+    // Key waiting for an indented block value.
+    let mut pending: Option<String> = None;
+    let mut block: Option<Value> = None;
+    // Indentation of the block lines, and whether the last table entry waits for a nested block.
+    let mut block_indent: Option<usize> = None;
+    let mut nested_open = false;
+    let finish_block =
+        |table: &mut Table, pending: &mut Option<String>, block: &mut Option<Value>| {
+            if let Some(key) = pending.take() {
+                let value = block.take().unwrap_or_else(|| Value::Scalar(String::new()));
+                table.push((key, value))
+            }
+        };
+    for (n, line) in text.lines().enumerate() {
+        let content = strip_comment(line, true).trim();
+        if content.is_empty() {
+            continue;
+        }
+        let indent = line.len() - line.trim_start().len();
+        if indent > 0 {
+            if pending.is_none() {
+                return Err(("Unexpected indentation".into(), n));
+            }
+            let block_indent = *block_indent.get_or_insert(indent);
+            if indent <= block_indent {
+                nested_open = push_block_line(&mut block, content, n)?;
+                continue;
+            }
+            // Nested block of the last table entry, which has no inline value
+            let entry = match &mut block {
+                Some(Value::Table(sub_table)) if nested_open => {
+                    &mut sub_table.last_mut().unwrap().1
+                }
+                _ => return Err(("Unexpected indentation".into(), n)),
+            };
+            let mut nested = match std::mem::replace(entry, Value::Scalar(String::new())) {
+                Value::Scalar(_) => None,
+                value => Some(value),
+            };
+            if push_block_line(&mut nested, content, n)? {
+                return Err(("Unsupported nesting depth".into(), n));
+            }
+            *entry = nested.unwrap();
+            continue;
+        }
+        finish_block(&mut table, &mut pending, &mut block);
+        block_indent = None;
+        nested_open = false;
+        let (key, value) = split_key_value(content, ':', n)?;
+        if value.is_empty() {
+            pending = Some(key)
+        } else {
+            table.push((key, parse_inline_value(value, n)?))
+        }
+    }
+    finish_block(&mut table, &mut pending, &mut block);
+    Ok(table)
+}
+
+pub fn parse_toml(text: &str) -> Result<Table, Error> {
+    let mut table = Table::new();
+    // Path of the current table header, empty for the root table.
+    let mut current: Vec<String> = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let content = strip_comment(line, false).trim();
+        if content.is_empty() {
+            continue;
+        }
+        if content.starts_with('[') {
+            let header = content
+                .strip_prefix('[')
+                .and_then(|c| c.strip_suffix(']'))
+                .ok_or_else(|| ("Malformed table header".to_string(), n))?;
+            current = header
+                .split('.')
+                .map(|part| parse_scalar(part.trim(), n))
+                .collect::<Result<_, _>>()?;
+            // Create the table now so that empty tables are present.
+            table_at(&mut table, &current, n)?;
+            continue;
+        }
+        let (key, value) = split_key_value(content, '=', n)?;
+        let key = parse_scalar(&key, n)?;
+        let value = parse_inline_value(value, n)?;
+        table_at(&mut table, &current, n)?.push((key, value))
+    }
+    Ok(table)
+}
+
+/// Add a "- item" or "key: value" line to a block value, created by the first line.
+/// Returns true for table entries without value, which may be followed by a nested block.
+fn push_block_line(block: &mut Option<Value>, content: &str, n: usize) -> Result<bool, Error> {
+    if let Some(item) = content.strip_prefix('-') {
+        match block.get_or_insert_with(|| Value::List(Vec::new())) {
+            Value::List(list) => list.push(parse_scalar(item.trim(), n)?),
+            _ => return Err(("List item in table".into(), n)),
+        }
+        return Ok(false);
+    }
+    let (key, value) = split_key_value(content, ':', n)?;
+    let opens_block = value.is_empty();
+    let value = parse_inline_value(value, n)?;
+    match block.get_or_insert_with(|| Value::Table(Table::new())) {
+        Value::Table(sub_table) => sub_table.push((key, value)),
+        _ => return Err(("Table entry in list".into(), n)),
+    }
+    Ok(opens_block)
+}
+
+/// Access a nested table by path, creating missing tables.
+fn table_at<'t>(table: &'t mut Table, path: &[String], n: usize) -> Result<&'t mut Table, Error> {
+    let (first, rest) = match path.split_first() {
+        None => return Ok(table),
+        Some(split) => split,
+    };
+    let position = match table.iter().position(|(k, _)| k == first) {
+        Some(position) => position,
+        None => {
+            table.push((first.clone(), Value::Table(Table::new())));
+            table.len() - 1
+        }
+    };
+    match &mut table[position].1 {
+        Value::Table(sub_table) => table_at(sub_table, rest, n),
+        _ => Err((format!("Key {} is not a table", first), n)),
+    }
+}
+
+/// Split "key <sep> value" at the first separator outside quotes.
+fn split_key_value(line: &str, separator: char, n: usize) -> Result<(String, &str), Error> {
+    let position = find_unquoted(line, separator)
+        .ok_or_else(|| (format!("Expected \"key {} value\"", separator), n))?;
+    let key = line[..position].trim();
+    if key.is_empty() {
+        return Err(("Empty key".into(), n));
+    }
+    Ok((unquote(key).to_string(), line[position + 1..].trim()))
+}
+
+fn parse_inline_value(value: &str, n: usize) -> Result<Value, Error> {
+    match value.strip_prefix('[') {
+        None => Ok(Value::Scalar(parse_scalar(value, n)?)),
+        Some(inner) => {
+            let inner = inner
+                .strip_suffix(']')
+                .ok_or_else(|| ("Unclosed list".to_string(), n))?;
+            let mut items = Vec::new();
+            let mut rest = inner.trim();
+            while !rest.is_empty() {
+                let (item, next) = match find_unquoted(rest, ',') {
+                    Some(comma) => (&rest[..comma], &rest[comma + 1..]),
+                    None => (rest, ""),
+                };
+                items.push(parse_scalar(item.trim(), n)?);
+                rest = next.trim()
+            }
+            Ok(Value::List(items))
+        }
+    }
+}
+
+/// Parse a plain or quoted scalar. Double quoted strings support \" \\ \n \t escapes.
+fn parse_scalar(s: &str, n: usize) -> Result<String, Error> {
+    if let Some(inner) = s.strip_prefix('"') {
+        let inner = inner
+            .strip_suffix('"')
+            .ok_or_else(|| ("Unclosed string".to_string(), n))?;
+        let mut string = String::with_capacity(inner.len());
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                string.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => string.push('\n'),
+                Some('t') => string.push('\t'),
+                Some(c @ '"') | Some(c @ '\\') => string.push(c),
+                _ => return Err(("Invalid escape sequence".into(), n)),
+            }
+        }
+        Ok(string)
+    } else if let Some(inner) = s.strip_prefix('\'') {
+        let inner = inner
+            .strip_suffix('\'')
+            .ok_or_else(|| ("Unclosed string".to_string(), n))?;
+        Ok(inner.to_string())
+    } else {
+        Ok(s.to_string())
+    }
+}
+
+fn unquote(s: &str) -> &str {
+    let quoted = |q| s.len() >= 2 && s.starts_with(q) && s.ends_with(q);
+    if quoted('"') || quoted('\'') {
+        &s[1..s.len() - 1]
+    } else {
+        s
+    }
+}
+
+/// Remove a comment from an unquoted '#' to the end of the line.
+/// If `after_space`, the '#' must start the line or follow a space, as in YAML.
+fn strip_comment(line: &str, after_space: bool) -> &str {
+    let mut start = 0;
+    while let Some(i) = find_unquoted(&line[start..], '#') {
+        let i = start + i;
+        if !after_space || i == 0 || line[..i].ends_with(char::is_whitespace) {
+            return &line[..i];
+        }
+        start = i + 1
+    }
+    line
+}
+
+/// Find the first occurrence of a char outside of quoted strings.
+/// Quotes only start a string at the start of a key, value or list item: "L'auberge" is plain text.
+fn find_unquoted(s: &str, target: char) -> Option<usize> {
+    let starts_scalar = |i: usize| {
+        let before = s[..i].trim_end();
+        before.is_empty()
+            || before.ends_with(&['[', ',', ':', '='][..])
+            || before.trim_start() == "-"
+    };
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match quote {
+            Some('"') if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None if (c == '"' || c == '\'') && starts_scalar(i) => quote = Some(c),
+            None if c == target => return Some(i),
+            None => (),
+        }
+    }
+    None
+}
+
+#[test]
+fn yaml() {
+    let scalar = |s: &str| Value::Scalar(s.into());
+    let list = |l: &[&str]| Value::List(l.iter().map(|s| s.to_string()).collect());
+    let text = "title: \"Campagne: acte 1\"\n# comment\ntags: [a, 'b, c']\nkeywords:\n  - Aldric\n  - Mira\naliases:\n  Aldric: [Al]\nempty:\n";
+    assert_eq!(
+        parse_yaml(text).unwrap(),
+        vec![
+            ("title".into(), scalar("Campagne: acte 1")),
+            ("tags".into(), list(&["a", "b, c"])),
+            ("keywords".into(), list(&["Aldric", "Mira"])),
+            (
+                "aliases".into(),
+                Value::Table(vec![("Aldric".into(), list(&["Al"]))])
+            ),
+            ("empty".into(), scalar("")),
+        ]
+    );
+    assert_eq!(parse_yaml("  - a").unwrap_err().1, 0);
+    assert_eq!(parse_yaml("a: 1\nb").unwrap_err().1, 1);
+
+    // Trailing comments, and nested blocks in a table
+    let text = "title: \"X\" # note\nurl: a#b\naliases: # by keyword\n  Aldric:\n    - Al\n    - 'Al #2'\n  Mira: [M]\n  Orage:\n    court: O\n";
+    assert_eq!(
+        parse_yaml(text).unwrap(),
+        vec![
+            ("title".into(), scalar("X")),
+            ("url".into(), scalar("a#b")),
+            (
+                "aliases".into(),
+                Value::Table(vec![
+                    ("Aldric".into(), list(&["Al", "Al #2"])),
+                    ("Mira".into(), list(&["M"])),
+                    (
+                        "Orage".into(),
+                        Value::Table(vec![("court".into(), scalar("O"))])
+                    ),
+                ])
+            ),
+        ]
+    );
+    assert_eq!(parse_yaml("a:\n  - b\n    - c\n").unwrap_err().1, 2);
+
+    // Apostrophes inside plain scalars are not quotes
+    let text = "title: L'auberge # note\nkeywords: [L'épée, Mira]\naliases:\n  L'Ours: [ours]\n";
+    assert_eq!(
+        parse_yaml(text).unwrap(),
+        vec![
+            ("title".into(), scalar("L'auberge")),
+            ("keywords".into(), list(&["L'épée", "Mira"])),
+            (
+                "aliases".into(),
+                Value::Table(vec![("L'Ours".into(), list(&["ours"]))])
+            ),
+        ]
+    );
+    assert_eq!(
+        parse_yaml("a:\n  b:\n    c:\n      d: e\n").unwrap_err().1,
+        2
+    );
+}
+
+#[test]
+fn toml() {
+    let scalar = |s: &str| Value::Scalar(s.into());
+    let text = "title = \"A \\\"B\\\"\"\nn = 3\n[a.b]\nx = [1, \"2\"]\n[a]\ny = true\n";
+    assert_eq!(
+        parse_toml(text).unwrap(),
+        vec![
+            ("title".into(), scalar("A \"B\"")),
+            ("n".into(), scalar("3")),
+            (
+                "a".into(),
+                Value::Table(vec![
+                    (
+                        "b".into(),
+                        Value::Table(vec![(
+                            "x".into(),
+                            Value::List(vec!["1".into(), "2".into()])
+                        )])
+                    ),
+                    ("y".into(), scalar("true")),
+                ])
+            ),
+        ]
+    );
+    let text = "# comment\ntitle = \"A # B\" # note\nx = [1, 2] # list\n";
+    assert_eq!(
+        parse_toml(text).unwrap(),
+        vec![
+            ("title".into(), scalar("A # B")),
+            ("x".into(), Value::List(vec!["1".into(), "2".into()])),
+        ]
+    );
+    assert_eq!(parse_toml("a = \"b").unwrap_err().1, 0);
+    assert_eq!(parse_toml("[a\n").unwrap_err().1, 0);
+}
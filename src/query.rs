@@ -0,0 +1,244 @@
+use crate::ast::InlineElement;
+use crate::document::{for_each_text_unit, IndexedDocument, KeywordIndex, TextUnitKind};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+/******************************************************************************
+ * Keyword queries.
+ *
+ * A query is a sequence of keywords separated by boolean operators, evaluated left to right:
+ * "a AND b NOT c" selects units containing a and b, but not c.
+ * Supported operators are AND, OR, NOT (upper case, as keywords may contain lower case words).
+ * Keywords may contain spaces, and are case insensitive.
+ *
+ * Queries are evaluated on text units (title, paragraph, list item).
+ * A unit matches a keyword if one of its inlines has an explicit or implicit occurrence.
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    And,
+    Or,
+    Not,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Query {
+    first: String,
+    rest: Vec<(Operator, String)>,
+}
+
+impl Query {
+    /// Parse a query from its text representation.
+    pub fn parse(text: &str) -> Result<Query, String> {
+        let mut keywords: Vec<String> = Vec::new();
+        let mut operators: Vec<Operator> = Vec::new();
+        let mut current: Vec<&str> = Vec::new();
+        for word in text.split_whitespace() {
+            let operator = match word {
+                "AND" => Operator::And,
+                "OR" => Operator::Or,
+                "NOT" => Operator::Not,
+                word => {
+                    current.push(word);
+                    continue;
+                }
+            };
+            if current.is_empty() {
+                return Err(format!("Query: missing keyword before {}", word));
+            }
+            keywords.push(current.join(" "));
+            current.clear();
+            operators.push(operator)
+        }
+        if current.is_empty() {
+            return Err("Query: missing keyword at end".into());
+        }
+        keywords.push(current.join(" "));
+        let mut keywords = keywords.into_iter();
+        Ok(Query {
+            first: keywords.next().unwrap(),
+            rest: operators.into_iter().zip(keywords).collect(),
+        })
+    }
+
+    /// Keywords of the query which are not negated.
+    fn positive_keywords(&self) -> impl Iterator<Item = &str> {
+        let rest = self.rest.iter().filter(|(op, _)| *op != Operator::Not);
+        std::iter::once(self.first.as_str()).chain(rest.map(|(_, k)| k.as_str()))
+    }
+}
+
+/// Set of text units where the keyword occurs.
+fn units_of_keyword(document: &IndexedDocument, keyword: KeywordIndex) -> BTreeSet<usize> {
+    let explicit = document.explicit_occurrences(keyword).iter();
+    let implicit = document.implicit_occurrences(keyword).iter();
+    explicit
+        .chain(implicit)
+        .map(|inline| document.inline_location(*inline).unit)
+        .collect()
+}
+
+fn keyword_index(document: &IndexedDocument, keyword: &str) -> Result<KeywordIndex, String> {
+    document
+        .keyword_index(keyword)
+        .ok_or_else(|| format!("Query: unknown keyword \"{}\"", keyword))
+}
+
+/// Evaluate a query to the set of matching text units.
+pub fn evaluate(document: &IndexedDocument, query: &Query) -> Result<BTreeSet<usize>, String> {
+    let mut units = units_of_keyword(document, keyword_index(document, &query.first)?);
+    for (operator, keyword) in &query.rest {
+        let other = units_of_keyword(document, keyword_index(document, keyword)?);
+        units = match operator {
+            Operator::And => units.intersection(&other).cloned().collect(),
+            Operator::Or => units.union(&other).cloned().collect(),
+            Operator::Not => units.difference(&other).cloned().collect(),
+        }
+    }
+    Ok(units)
+}
+
+/// Kind of occurrence of a keyword in a unit, as displayed.
+fn occurrence_kind(
+    document: &IndexedDocument,
+    keyword: KeywordIndex,
+    inlines: &[InlineElement],
+) -> Option<&'static str> {
+    let contains = |occurrences: &[usize]| {
+        inlines
+            .iter()
+            .any(|inline| occurrences.binary_search(&inline.index).is_ok())
+    };
+    if contains(document.explicit_occurrences(keyword)) {
+        Some("explicit")
+    } else if contains(document.implicit_occurrences(keyword)) {
+        Some("implicit")
+    } else {
+        None
+    }
+}
+
+/// Text units matching the query, with section breadcrumb and source location.
+pub fn format_matches(document: &IndexedDocument, query: &Query) -> Result<String, String> {
+    let mut out = String::new();
+    let units = evaluate(document, query)?;
+    let keywords = query
+        .positive_keywords()
+        .map(|k| keyword_index(document, k))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut unit = 0;
+    for_each_text_unit(document.document(), &mut |path, kind, inlines| {
+        if units.contains(&unit) {
+            let hits: Vec<String> = keywords
+                .iter()
+                .filter_map(|k| {
+                    let kind = occurrence_kind(document, *k, inlines)?;
+                    Some(format!("{} {}", kind, document.keyword(*k)))
+                })
+                .collect();
+            let (source, line) = document.document().source_location(inlines[0].line);
+            writeln!(
+                out,
+                "{} ({}:{}) [{}]",
+                document.section_display_name(path),
+                source,
+                line,
+                hits.join(", ")
+            )
+            .unwrap();
+            let prefix = match kind {
+                TextUnitKind::Title(level) => "#".repeat(level) + " ",
+                TextUnitKind::Paragraph => String::new(),
+                TextUnitKind::TableCell { .. } => "| ".to_string(),
+                TextUnitKind::ListItem { ordered, depth } => {
+                    "  ".repeat(depth) + if ordered { "1. " } else { "- " }
+                }
+            };
+            for (i, inline) in inlines.iter().enumerate() {
+                let prefix = if i == 0 { prefix.as_str() } else { "" };
+                writeln!(out, "{}{}", prefix, inline.string).unwrap()
+            }
+            writeln!(out).unwrap()
+        }
+        unit += 1
+    });
+    Ok(out)
+}
+
+#[test]
+fn query_parsing() {
+    assert_eq!(
+        Query::parse("mot clé AND wimd NOT  rust OR b").unwrap(),
+        Query {
+            first: "mot clé".into(),
+            rest: vec![
+                (Operator::And, "wimd".into()),
+                (Operator::Not, "rust".into()),
+                (Operator::Or, "b".into())
+            ]
+        }
+    );
+    assert!(Query::parse("").is_err());
+    assert!(Query::parse("AND a").is_err());
+    assert!(Query::parse("a NOT").is_err());
+}
+
+#[cfg(test)]
+fn test_document() -> IndexedDocument {
+    let text = "\
+# *Valombre* #
+Une ville tenue par *Mira*.
+
+## Port ##
+Mira garde le port de valombre.
+
+- *Orage* sur valombre
+";
+    let (document, keywords) = crate::ast::parse(text).unwrap();
+    IndexedDocument::from(document, keywords)
+}
+
+#[test]
+fn evaluate_operators() {
+    let document = test_document();
+    let units = |query: &str| {
+        let query = Query::parse(query).unwrap();
+        evaluate(&document, &query)
+            .unwrap()
+            .into_iter()
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(units("valombre"), vec![0, 3, 4]);
+    assert_eq!(units("mira"), vec![1, 3]);
+    assert_eq!(units("Valombre AND Mira"), vec![3]);
+    assert_eq!(units("Valombre NOT Mira"), vec![0, 4]);
+    assert_eq!(units("Mira OR Orage"), vec![1, 3, 4]);
+    assert_eq!(units("Valombre AND Orage NOT Mira"), vec![4]);
+    assert!(evaluate(&document, &Query::parse("Inconnu").unwrap()).is_err());
+}
+
+#[test]
+fn format_matches_hits() {
+    let document = test_document();
+    let matches = |query: &str| format_matches(&document, &Query::parse(query).unwrap()).unwrap();
+    assert_eq!(
+        matches("Valombre AND Mira"),
+        "\
+Valombre > Port (<text>:5) [implicit Valombre, implicit Mira]
+Mira garde le port de valombre.
+
+"
+    );
+    assert_eq!(
+        matches("Mira NOT Orage"),
+        "\
+Valombre (<text>:2) [explicit Mira]
+Une ville tenue par Mira.
+
+Valombre > Port (<text>:5) [implicit Mira]
+Mira garde le port de valombre.
+
+"
+    );
+}
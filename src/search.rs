@@ -0,0 +1,185 @@
+use crate::ast::InlineTag;
+use crate::document::{for_each_text_unit, IndexedDocument, KeywordIndex, TextUnitKind};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+/******************************************************************************
+ * Client-side search index for the html wiki.
+ *
+ * The index is a JSON object with arrays of compact entries:
+ * - "keywords": [name, page] for each keyword, in keyword index order.
+ * - "aliases": [alias, keyword] with the keyword index.
+ * - "sections": [title, page with anchor] for each section.
+ * - "snippets": [sentence, [keywords]] for each sentence with keyword occurrences, with keyword indexes.
+ *
+ * It is written as a script assigning the object to a global variable,
+ * as browsers refuse to fetch JSON files from pages opened from the file system.
+ * The search script matches the query as a substring, ignoring case and accents.
+ */
+
+/// Maximum length in chars of sentence snippets.
+const MAX_SNIPPET_LENGTH: usize = 200;
+
+pub const SEARCH_SCRIPT: &str = r#"(function () {
+    var index = window.WIMD_SEARCH_INDEX;
+    var input = document.getElementById('search');
+    var results = document.getElementById('search-results');
+    if (!index || !input || !results) { return; }
+    var fold = function (s) { return s.normalize('NFD').replace(/[\u0300-\u036f]/g, '').toLowerCase(); };
+    var escape = function (s) {
+        return s.replace(/&/g, '&amp;').replace(/</g, '&lt;').replace(/>/g, '&gt;').replace(/"/g, '&quot;');
+    };
+    var link = function (page, text) { return '<a href="' + page + '">' + escape(text) + '</a>'; };
+    var keywordLink = function (k) { return link(index.keywords[k][1], index.keywords[k][0]); };
+    input.addEventListener('input', function () {
+        var query = fold(input.value.trim());
+        var items = [];
+        if (query.length > 0) {
+            index.keywords.forEach(function (k, i) {
+                if (fold(k[0]).indexOf(query) >= 0) { items.push(keywordLink(i)); }
+            });
+            index.aliases.forEach(function (a) {
+                if (fold(a[0]).indexOf(query) >= 0) { items.push(escape(a[0]) + ' → ' + keywordLink(a[1])); }
+            });
+            index.sections.forEach(function (s) {
+                if (fold(s[0]).indexOf(query) >= 0) { items.push('§ ' + link(s[1], s[0])); }
+            });
+            index.snippets.forEach(function (s) {
+                if (fold(s[0]).indexOf(query) >= 0) { items.push(escape(s[0]) + ' ' + s[1].map(keywordLink).join(', ')); }
+            });
+        }
+        results.innerHTML = items.slice(0, 30).map(function (item) { return '<li>' + item + '</li>'; }).join('');
+    });
+})();
+"#;
+
+/// Script defining the search index.
+/// Page names are given by functions, to stay consistent with the wiki pages.
+pub fn search_index_script(
+    document: &IndexedDocument,
+    keyword_page: &dyn Fn(KeywordIndex) -> String,
+    section_page: &dyn Fn(&[usize]) -> String,
+) -> String {
+    let mut entries: Vec<String> = Vec::new();
+    let keywords = (0..document.keywords().len())
+        .map(|k| format!("[{},{}]", json(document.keyword(k)), json(&keyword_page(k))));
+    entries.push(format!("\"keywords\":[{}]", join(keywords)));
+
+    let aliases = &document.document().front_matter.aliases;
+    let aliases = aliases.iter().flat_map(|(keyword, aliases)| {
+        let keyword = document.keyword_index(keyword);
+        aliases
+            .iter()
+            .filter_map(move |alias| Some(format!("[{},{}]", json(alias), keyword?)))
+    });
+    entries.push(format!("\"aliases\":[{}]", join(aliases)));
+
+    let mut sections = Vec::new();
+    let mut snippets = Vec::new();
+    for_each_text_unit(document.document(), &mut |path, kind, inlines| {
+        if let TextUnitKind::Title(_) = kind {
+            let title = &inlines[0].string;
+            sections.push(format!("[{},{}]", json(title), json(&section_page(path))));
+            return;
+        }
+        for inline in inlines {
+            for (sentence, keywords) in sentences_with_keywords(&inline.string, &inline.tags) {
+                let keywords: Vec<String> = keywords.iter().map(|k| k.to_string()).collect();
+                let sentence = truncate(sentence, MAX_SNIPPET_LENGTH);
+                snippets.push(format!("[{},[{}]]", json(&sentence), keywords.join(",")))
+            }
+        }
+    });
+    entries.push(format!("\"sections\":[{}]", join(sections)));
+    entries.push(format!("\"snippets\":[{}]", join(snippets)));
+    format!("window.WIMD_SEARCH_INDEX = {{{}}};\n", entries.join(",\n"))
+}
+
+fn join<I: IntoIterator<Item = String>>(items: I) -> String {
+    items.into_iter().collect::<Vec<_>>().join(",\n")
+}
+
+/// Sentences of a text containing keyword occurrences, with their keywords.
+fn sentences_with_keywords<'t>(
+    text: &'t str,
+    tags: &[(std::ops::Range<usize>, InlineTag)],
+) -> Vec<(&'t str, BTreeSet<KeywordIndex>)> {
+    let mut sentences = Vec::new();
+    for range in sentence_ranges(text) {
+        let keywords: BTreeSet<KeywordIndex> = tags
+            .iter()
+            .filter(|(tag_range, _)| range.contains(&tag_range.start))
+            .filter_map(|(_, tag)| match tag {
+                InlineTag::ExplicitKeyword(k) | InlineTag::ImplicitKeyword(k) => Some(*k),
+                InlineTag::Highlight | InlineTag::Secret(_) => None,
+            })
+            .collect();
+        let sentence = text[range].trim();
+        if !keywords.is_empty() && !sentence.is_empty() {
+            sentences.push((sentence, keywords))
+        }
+    }
+    sentences
+}
+
+/// Consecutive ranges of the sentences of a text, untrimmed.
+pub fn sentence_ranges(text: &str) -> Vec<std::ops::Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    while start < text.len() {
+        // A sentence ends after a terminal punctuation followed by a space, or at the end of text
+        let end = text[start..]
+            .match_indices(['.', '!', '?'])
+            .map(|(i, p)| start + i + p.len())
+            .find(|end| text[*end..].starts_with(' '))
+            .unwrap_or(text.len());
+        ranges.push(start..end);
+        start = end
+    }
+    ranges
+}
+
+fn truncate(s: &str, max_chars: usize) -> String {
+    match s.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", &s[..end]),
+        None => s.to_string(),
+    }
+}
+
+/// Quoted JSON string.
+fn json(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            // Avoid closing the script element early, and other control chars
+            '<' => quoted.push_str("\\u003c"),
+            c if (c as u32) < 0x20 => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[test]
+fn search_index() {
+    let text =
+        "---\naliases:\n  Mira: [la mage]\n---\n# Lieux #\nIci *Mira* \"parle\". Rien. Mira part\n";
+    let (document, keywords) = crate::ast::parse(text).unwrap();
+    let document = IndexedDocument::from(document, keywords);
+    let script = search_index_script(&document, &|k| format!("k{}.html", k), &|path| {
+        format!("index.html#{:?}", path)
+    });
+    let expected = r#"window.WIMD_SEARCH_INDEX = {"keywords":[["Mira","k0.html"]],
+"aliases":[["la mage",0]],
+"sections":[["Lieux","index.html#[0]"]],
+"snippets":[["Ici Mira \"parle\".",[0]],
+["Mira part",[0]]]};
+"#;
+    assert_eq!(script, expected);
+    assert_eq!(json("a<\t"), r#""a\u003c\u0009""#);
+}
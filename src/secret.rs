@@ -0,0 +1,422 @@
+use crate::ast::*;
+use crate::document::{for_each_text_unit, for_each_text_unit_mut};
+use std::collections::HashSet;
+use std::ops::Range;
+use unicase::UniCase;
+
+/******************************************************************************
+ * Removal of secret content, to share the document with players.
+ *
+ * Secret content is marked during parsing, see the ast module, with the audiences allowed to see it.
+ * Audiences are names, with some conventions:
+ * - "gm", the game master, sees everything.
+ * - "player:<name>" also sees content for "party".
+ * - "public" is used by the strip_secrets option, and usually sees only content which is not secret.
+ * When the document is filtered for an audience, secret markers are removed, and invisible content:
+ * - secret ranges are removed from inlines, with the spaces separating them from the remaining text.
+ *   Keyword occurrences containing secret text are removed, as their name would reveal it.
+ * - text units which are entirely secret are removed: paragraphs, list items with their sub list,
+ *   and section titles with their whole section. Secret table cells are emptied,
+ *   and rows left empty are removed. Secret code blocks are removed.
+ * - inlines are renumbered in order of appearance.
+ * - keywords are kept if they have an explicit occurrence left, or if they are only declared in front matter.
+ *   Removed keywords are also removed from front matter declarations and aliases.
+ * Sections extend until the next header, so text after a secret section is removed with it,
+ * even if it comes from another source.
+ */
+
+pub const GAME_MASTER: &str = "gm";
+pub const PARTY: &str = "party";
+pub const PLAYER_PREFIX: &str = "player:";
+pub const PUBLIC: &str = "public";
+
+/// Test if secret content for the given audiences is visible to an audience.
+pub fn is_visible(audience: &str, audiences: &[String]) -> bool {
+    let is_player = audience.starts_with(PLAYER_PREFIX);
+    audience == GAME_MASTER
+        || audiences
+            .iter()
+            .any(|allowed| allowed == audience || (is_player && allowed == PARTY))
+}
+
+/// Audience seeing the least content among the given ones: "public", then other audiences,
+/// then players (who also see content for "party"), then the game master. The first one if equal.
+pub fn most_restricted(audiences: &[String]) -> Option<&String> {
+    let rank = |audience: &&String| match audience.as_str() {
+        PUBLIC => 0,
+        GAME_MASTER => 3,
+        a if a.starts_with(PLAYER_PREFIX) => 2,
+        _ => 1,
+    };
+    audiences.iter().min_by_key(rank)
+}
+
+/// Document and keywords with only the content visible to the audience.
+pub fn for_audience(
+    document: Document,
+    keywords: KeywordSet,
+    audience: &str,
+) -> (Document, KeywordSet) {
+    let mut all_occurrences = HashSet::new();
+    for_each_text_unit(&document, &mut |_, _, inlines| {
+        all_occurrences.extend(explicit_occurrences(inlines))
+    });
+    let mut stripper = Stripper {
+        audience,
+        inline_count: 0,
+        occurrences: HashSet::new(),
+    };
+    let content = stripper.content(document.content);
+
+    // Keywords only occurring in secret content are removed, others keep their relative order
+    let kept = |k: &usize| stripper.occurrences.contains(k) || !all_occurrences.contains(k);
+    let mut new_indexes = vec![None; keywords.len()];
+    let mut stripped_keywords = KeywordSet::new();
+    for (k, keyword) in keywords.iter().enumerate().filter(|(k, _)| kept(k)) {
+        new_indexes[k] = Some(stripped_keywords.insert_full(keyword.clone()).0)
+    }
+
+    let mut front_matter = document.front_matter;
+    let is_kept = |name: &String| {
+        let index = keywords.get_full(&UniCase::new(name.clone()));
+        index.map_or(true, |(k, _)| new_indexes[k].is_some())
+    };
+    front_matter.keywords.retain(is_kept);
+    front_matter.aliases.retain(|(keyword, _)| is_kept(keyword));
+    let mut document = Document {
+        front_matter,
+        content,
+        sources: document.sources,
+    };
+    for_each_text_unit_mut(&mut document, &mut |_, _, inlines| {
+        for inline in inlines {
+            for (_, tag) in &mut inline.tags {
+                if let InlineTag::ExplicitKeyword(k) = tag {
+                    *k = new_indexes[*k].unwrap()
+                }
+            }
+        }
+    });
+    (document, stripped_keywords)
+}
+
+struct Stripper<'a> {
+    audience: &'a str,
+    inline_count: usize,
+    /// Keywords with explicit occurrences in the stripped document.
+    occurrences: HashSet<usize>,
+}
+
+impl Stripper<'_> {
+    fn content(&mut self, content: SectionContent) -> SectionContent {
+        let blocks = self.blocks(content.blocks);
+        let sub_sections = content
+            .sub_sections
+            .into_iter()
+            .filter_map(|section| {
+                let title = self.inline(section.title)?;
+                let content = self.content(section.content);
+                Some(Section { title, content })
+            })
+            .collect();
+        SectionContent {
+            blocks,
+            sub_sections,
+        }
+    }
+
+    fn blocks(&mut self, blocks: Vec<BlockElement>) -> Vec<BlockElement> {
+        blocks
+            .into_iter()
+            .filter_map(|block| match block {
+                BlockElement::Paragraph(inlines) => {
+                    let inlines = self.inlines(inlines);
+                    match inlines.is_empty() {
+                        true => None,
+                        false => Some(BlockElement::Paragraph(inlines)),
+                    }
+                }
+                BlockElement::Code {
+                    secret: Some(audiences),
+                    ..
+                } if !is_visible(self.audience, &audiences) => None,
+                BlockElement::Code {
+                    lang, text, line, ..
+                } => Some(BlockElement::Code {
+                    lang,
+                    text,
+                    line,
+                    secret: None,
+                }),
+                BlockElement::Rule => Some(BlockElement::Rule),
+                BlockElement::List(list) => self.list(list).map(BlockElement::List),
+                BlockElement::Quote(blocks) => {
+                    let blocks = self.blocks(blocks);
+                    match blocks.is_empty() {
+                        true => None,
+                        false => Some(BlockElement::Quote(blocks)),
+                    }
+                }
+                BlockElement::Table(table) => self.table(table).map(BlockElement::Table),
+            })
+            .collect()
+    }
+
+    fn list(&mut self, list: List) -> Option<List> {
+        let mut items = Vec::new();
+        for item in list.items {
+            let text_content = self.inlines(item.text_content);
+            if text_content.is_empty() {
+                continue;
+            }
+            let sub_list = item.sub_list.and_then(|sub_list| self.list(sub_list));
+            items.push(ListItem {
+                text_content,
+                sub_list,
+            })
+        }
+        match items.is_empty() {
+            true => None,
+            false => Some(List {
+                ordered: list.ordered,
+                items,
+            }),
+        }
+    }
+
+    /// Table with secret cells emptied, without rows left empty, or None if nothing is left.
+    fn table(&mut self, table: Table) -> Option<Table> {
+        let header = self.cells(table.header);
+        let rows: Vec<Vec<TableCell>> = (table.rows.into_iter())
+            .map(|row| self.cells(row))
+            .filter(|row| row.iter().any(|cell| !cell.is_empty()))
+            .collect();
+        match rows.is_empty() && header.iter().all(Vec::is_empty) {
+            true => None,
+            false => Some(Table {
+                alignments: table.alignments,
+                header,
+                rows,
+            }),
+        }
+    }
+    fn cells(&mut self, cells: Vec<TableCell>) -> Vec<TableCell> {
+        cells.into_iter().map(|cell| self.inlines(cell)).collect()
+    }
+
+    fn inlines(&mut self, inlines: Vec<InlineElement>) -> Vec<InlineElement> {
+        inlines
+            .into_iter()
+            .filter_map(|inline| self.inline(inline))
+            .collect()
+    }
+
+    /// Inline without its secret ranges, or None if nothing is left.
+    fn inline(&mut self, inline: InlineElement) -> Option<InlineElement> {
+        let string = &inline.string;
+        let mut removed: Vec<Range<usize>> = inline
+            .tags
+            .iter()
+            .filter_map(|(range, tag)| match tag {
+                InlineTag::Secret(audiences) if !is_visible(self.audience, audiences) => {
+                    Some(range)
+                }
+                _ => None,
+            })
+            .map(|range| {
+                let before = string[..range.start].trim_end().len();
+                let after = string.len() - string[range.end..].trim_start().len();
+                match (before < range.start || range.start == 0, after > range.end) {
+                    (true, true) => range.start..after,
+                    (true, false) => before..range.end,
+                    (false, _) => range.clone(),
+                }
+            })
+            .collect();
+        removed.sort_by_key(|range| range.start);
+        let intersects = |range: &Range<usize>| {
+            removed
+                .iter()
+                .any(|r| r.start < range.end && range.start < r.end)
+        };
+        // Offsets inside removed ranges are moved to their start
+        let shifted = |offset: usize| {
+            let before: usize = removed
+                .iter()
+                .map(|r| offset.min(r.end).saturating_sub(r.start))
+                .sum();
+            offset - before
+        };
+        let mut stripped = String::with_capacity(string.len());
+        let mut position = 0;
+        for range in &removed {
+            stripped.push_str(&string[position..range.start.max(position)]);
+            position = position.max(range.end)
+        }
+        stripped.push_str(&string[position..]);
+        if stripped.trim().is_empty() {
+            return None;
+        }
+
+        let mut tags = Vec::new();
+        for (range, tag) in inline.tags {
+            let range = match tag {
+                InlineTag::Secret(_) => continue,
+                InlineTag::ExplicitKeyword(_) | InlineTag::ImplicitKeyword(_)
+                    if intersects(&range) =>
+                {
+                    continue
+                }
+                _ => shifted(range.start)..shifted(range.end),
+            };
+            if !range.is_empty() {
+                tags.push((range, tag))
+            }
+        }
+        let index = self.inline_count;
+        self.inline_count += 1;
+        let inline = InlineElement {
+            index,
+            line: inline.line,
+            string: stripped,
+            tags,
+        };
+        self.occurrences
+            .extend(explicit_occurrences(std::slice::from_ref(&inline)));
+        Some(inline)
+    }
+}
+
+fn explicit_occurrences(inlines: &[InlineElement]) -> impl Iterator<Item = usize> + '_ {
+    inlines.iter().flat_map(|inline| {
+        inline.tags.iter().filter_map(|(_, tag)| match tag {
+            InlineTag::ExplicitKeyword(k) => Some(*k),
+            _ => None,
+        })
+    })
+}
+
+#[test]
+fn audiences() {
+    let text = "\
+# Lieux #
+*Mira* garde la porte ||de *Valombre*||. La porte ||est piégée||.
+
+[secret: party] *Orage* menace ||[secret: player:alice] Alice||.
+
+- *Mira* voit
+- [secret]
+  *Orage* arrive
+  - sous-élément
+
+[secret] *Orage* :
+
+```statblock
+PV: 3
+```
+
+```statblock [secret: party]
+PV: 4
+```
+
+# Trésor [secret: player:bob] #
+*Orage* et *Mira*
+";
+    let sources = [
+        Source {
+            name: "a.md".into(),
+            text: text.into(),
+        },
+        Source {
+            name: "b.md".into(),
+            text: "---\nsecret: true\ntitle: Secrets\nkeywords: [Valombre, Traitre]\naliases:\n  Mira: [La mage]\n---\n```\nPV: 9\n```\n# Cachette #\n*Cachette*\n".into(),
+        },
+    ];
+    let (document, keywords, _) = parse_sources(&sources, &ParseOptions::default()).unwrap();
+    let names: Vec<&str> = keywords.iter().map(|k| k.as_ref()).collect();
+    assert_eq!(
+        names,
+        vec!["Valombre", "Traitre", "Mira", "Orage", "Cachette"]
+    );
+    assert_eq!(document.front_matter.title.as_deref(), Some("Secrets"));
+    let title = &document.content.sub_sections[1].title;
+    assert_eq!(title.string, "Trésor");
+    assert!(
+        matches!(&title.tags[..], [(r, InlineTag::Secret(a))] if *r == (0..7) && a == &["player:bob"])
+    );
+    let title = &document.content.sub_sections[2].title;
+    assert!(matches!(&title.tags[..], [(r, InlineTag::Secret(a))] if *r == (0..8) && a.is_empty()));
+
+    let build = |audience: &str| {
+        let options = ParseOptions {
+            audience: Some(audience.to_string()),
+            ..ParseOptions::default()
+        };
+        let (document, keywords, _) = parse_sources(&sources, &options).unwrap();
+        let mut units = Vec::new();
+        for_each_text_unit(&document, &mut |_, _, inlines| {
+            for inline in inlines {
+                units.push(format!("{} {}", inline.index, inline.string))
+            }
+        });
+        fn code_count(content: &SectionContent) -> usize {
+            let blocks = content.blocks.iter();
+            let count = blocks
+                .filter(|b| matches!(b, BlockElement::Code { .. }))
+                .count();
+            count
+                + content
+                    .sub_sections
+                    .iter()
+                    .map(|s| code_count(&s.content))
+                    .sum::<usize>()
+        }
+        assert_eq!(
+            code_count(&document.content),
+            match audience {
+                GAME_MASTER => 3,
+                PUBLIC => 0,
+                _ => 1,
+            }
+        );
+        let keywords: Vec<String> = keywords.iter().map(|k| k.to_string()).collect();
+        (keywords.join(" "), units.join(" / "))
+    };
+    let (keywords, units) = build(PUBLIC);
+    assert_eq!(keywords, "Mira");
+    assert_eq!(
+        units,
+        "0 Lieux / 1 Mira garde la porte. La porte. / 2 Mira voit"
+    );
+    let (keywords, units) = build("player:alice");
+    assert_eq!(keywords, "Mira Orage");
+    assert_eq!(
+        units,
+        "0 Lieux / 1 Mira garde la porte. La porte. / 2 Orage menace Alice. / 3 Mira voit"
+    );
+    let (keywords, units) = build("player:bob");
+    assert_eq!(keywords, "Mira Orage");
+    assert!(units.ends_with("2 Orage menace. / 3 Mira voit / 4 Trésor / 5 Orage et Mira"));
+    let audiences = |names: &[&str]| names.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+    let restricted = audiences(&["gm", "player:alice", "party", "villains"]);
+    assert_eq!(most_restricted(&restricted).unwrap(), "party");
+    assert_eq!(
+        most_restricted(&audiences(&["gm", "public"])).unwrap(),
+        PUBLIC
+    );
+    let (keywords, _) = build(GAME_MASTER);
+    assert_eq!(keywords, "Valombre Traitre Mira Orage Cachette");
+    let options = ParseOptions {
+        audience: Some(PUBLIC.to_string()),
+        ..ParseOptions::default()
+    };
+    let (document, _, _) = parse_sources(&sources, &options).unwrap();
+    assert_eq!(document.front_matter.title, None);
+    assert!(document.front_matter.keywords.is_empty());
+    assert!(document.front_matter.aliases.is_empty());
+
+    let error = parse("a ||b\n").unwrap_err();
+    assert_eq!(error, "<text>:1: Unclosed secret range");
+    let error = parse("*a||b* c||\n").unwrap_err();
+    assert_eq!(error, "<text>:1: Secret range crossing emphasis or strong");
+}
@@ -0,0 +1,186 @@
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/******************************************************************************
+ * Local preview http server.
+ *
+ * Serves wiki pages rendered in memory, only on the loopback interface.
+ * The content type is given by the file extension.
+ * Html pages get a small script polling the site version, and reloading the page when it changes.
+ * The version is increased each time pages are replaced, or a build error is reported.
+ * A build error is shown as a banner on all pages, which keep their last successful content.
+ *
+ * Only GET requests are supported, with one request per connection.
+ */
+
+/// Path of the site version, polled by the live reload script.
+const VERSION_PATH: &str = "/__wimd/version";
+
+const LIVE_RELOAD_SCRIPT: &str = "<script>
+(function () {
+    var version = null;
+    setInterval(function () {
+        fetch('/__wimd/version').then(function (response) { return response.text(); }).then(function (v) {
+            if (version === null) { version = v; } else if (v !== version) { location.reload(); }
+        }).catch(function () {});
+    }, 1000);
+})();
+</script>
+";
+
+#[derive(Debug, Default)]
+pub struct Site {
+    version: u64,
+    pages: BTreeMap<String, String>,
+    error: Option<String>,
+}
+
+impl Site {
+    pub fn update(&mut self, pages: BTreeMap<String, String>) {
+        self.pages = pages;
+        self.error = None;
+        self.version += 1
+    }
+    pub fn set_error(&mut self, error: String) {
+        self.error = Some(error);
+        self.version += 1
+    }
+
+    /// Status, content type and body of the response for a request path.
+    fn respond(&self, path: &str) -> (&'static str, &'static str, String) {
+        let path = path.split(['?', '#']).next().unwrap_or("");
+        if path == VERSION_PATH {
+            return ("200 OK", "text/plain", self.version.to_string());
+        }
+        let name = match path.trim_start_matches('/') {
+            "" => "index.html",
+            name => name,
+        };
+        let page = match self.pages.get(name) {
+            Some(page) => page,
+            None => {
+                return (
+                    "404 Not Found",
+                    "text/plain",
+                    format!("{}: not found", name),
+                )
+            }
+        };
+        let content_type = match name.rsplit('.').next() {
+            Some("html") => "text/html; charset=utf-8",
+            Some("css") => "text/css",
+            Some("js") => "application/javascript",
+            _ => "text/plain; charset=utf-8",
+        };
+        if !name.ends_with(".html") {
+            return ("200 OK", content_type, page.clone());
+        }
+        let mut injected = LIVE_RELOAD_SCRIPT.to_string();
+        if let Some(error) = &self.error {
+            injected = format!(
+                "<pre style=\"background: #fdd; padding: 1em;\">{}</pre>\n{}",
+                escape(error),
+                injected
+            )
+        }
+        let page = match page.rfind("</body>") {
+            Some(end) => format!("{}{}{}", &page[..end], injected, &page[end..]),
+            None => format!("{}{}", page, injected),
+        };
+        ("200 OK", "text/html; charset=utf-8", page)
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Listen on the loopback interface, and serve the site from a background thread.
+pub fn start(site: Arc<Mutex<Site>>, port: u16) -> io::Result<()> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    eprintln!("Serving on http://{}", listener.local_addr()?);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let site = Arc::clone(&site);
+            thread::spawn(move || {
+                if let Err(error) = handle(&site, stream) {
+                    eprintln!("warning: http connection: {}", error)
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+fn handle(site: &Mutex<Site>, stream: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Skip headers
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear()
+    }
+    let mut parts = request_line.split_whitespace();
+    let (status, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some(path)) => site.lock().unwrap().respond(path),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "Only GET is supported".to_string(),
+        ),
+    };
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body.as_bytes())?;
+    stream.flush()
+}
+
+#[test]
+fn responses() {
+    let mut site = Site::default();
+    let mut pages = BTreeMap::new();
+    pages.insert("index.html".to_string(), "<body>a</body>".to_string());
+    pages.insert("style.css".to_string(), "body {}".to_string());
+    site.update(pages);
+    assert_eq!(site.respond(VERSION_PATH).2, "1");
+    let (status, _, body) = site.respond("/?x=1");
+    assert_eq!(status, "200 OK");
+    assert!(body.starts_with("<body>a<script>"));
+    assert!(body.ends_with("</script>\n</body>"));
+    assert_eq!(site.respond("/style.css").2, "body {}");
+    assert_eq!(site.respond("/missing.html").0, "404 Not Found");
+
+    site.set_error("a.md:1: <error>".into());
+    assert_eq!(site.respond(VERSION_PATH).2, "2");
+    assert!(site
+        .respond("/index.html")
+        .2
+        .contains("a.md:1: &lt;error&gt;"));
+}
+
+#[test]
+fn content_types() {
+    let mut site = Site::default();
+    let mut pages = BTreeMap::new();
+    pages.insert("search.js".to_string(), "var a = 1;".to_string());
+    pages.insert("search-index.js".to_string(), "var b = [];".to_string());
+    site.update(pages);
+    site.set_error("error".into());
+    let (status, content_type, body) = site.respond("/search.js");
+    assert_eq!(status, "200 OK");
+    assert_eq!(content_type, "application/javascript");
+    assert_eq!(body, "var a = 1;");
+    assert_eq!(site.respond("/search-index.js").2, "var b = [];");
+}
@@ -0,0 +1,315 @@
+/******************************************************************************
+ * Logic-less html templates, using a subset of the mustache syntax.
+ *
+ * - {{name}}: value of a field, html escaped.
+ * - {{{name}}}: value of a field, as raw html.
+ * - {{#name}}...{{/name}}: for a list, rendered for each item, which becomes the current context.
+ *   For a map, rendered once with the map as current context.
+ *   For a text or boolean, rendered once if not empty or true.
+ * - {{^name}}...{{/name}}: rendered once if the field is missing, or an empty list, an empty text, or false.
+ * - {{! comment }}: ignored.
+ * - {{.}}: the current context itself, for lists of texts.
+ *
+ * Fields are searched in the current context, then in enclosing ones. Missing fields are rendered empty.
+ * Section and comment tags alone on their line are removed with the line.
+ */
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Text(String),
+    Bool(bool),
+    List(Vec<Value>),
+    Map(Context),
+}
+
+/// Named fields given to a template.
+#[derive(Debug, Clone, Default)]
+pub struct Context {
+    fields: Vec<(&'static str, Value)>,
+}
+
+impl Context {
+    pub fn new() -> Context {
+        Context::default()
+    }
+    pub fn with<V: Into<Value>>(mut self, name: &'static str, value: V) -> Context {
+        self.fields.push((name, value.into()));
+        self
+    }
+    fn get(&self, name: &str) -> Option<&Value> {
+        let field = self.fields.iter().find(|(n, _)| *n == name);
+        field.map(|(_, value)| value)
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::Text(s)
+    }
+}
+impl From<&str> for Value {
+    fn from(s: &str) -> Value {
+        Value::Text(s.to_string())
+    }
+}
+impl From<usize> for Value {
+    fn from(n: usize) -> Value {
+        Value::Text(n.to_string())
+    }
+}
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::Bool(b)
+    }
+}
+impl From<Context> for Value {
+    fn from(context: Context) -> Value {
+        Value::Map(context)
+    }
+}
+impl<V: Into<Value>> From<Vec<V>> for Value {
+    fn from(items: Vec<V>) -> Value {
+        Value::List(items.into_iter().map(Into::into).collect())
+    }
+}
+/// Missing values are false.
+impl<V: Into<Value>> From<Option<V>> for Value {
+    fn from(value: Option<V>) -> Value {
+        value.map_or(Value::Bool(false), Into::into)
+    }
+}
+
+impl Value {
+    fn is_truthy(&self) -> bool {
+        match self {
+            Value::Text(s) => !s.is_empty(),
+            Value::Bool(b) => *b,
+            Value::List(items) => !items.is_empty(),
+            Value::Map(_) => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String),
+    Field {
+        name: String,
+        raw: bool,
+    },
+    Section {
+        name: String,
+        inverted: bool,
+        nodes: Vec<Node>,
+    },
+}
+
+/// Section being parsed: name, inverted, nodes of the enclosing level, line of the opening tag.
+type OpenSection = (String, bool, Vec<Node>, usize);
+
+impl Template {
+    /// Parse a template. Errors are prefixed by the template name and line.
+    pub fn parse(name: &str, text: &str) -> Result<Template, String> {
+        let error = |position: usize, message: String| {
+            let line = text[..position].matches('\n').count() + 1;
+            format!("{}:{}: {}", name, line, message)
+        };
+        let mut open_sections: Vec<OpenSection> = Vec::new();
+        let mut nodes = Vec::new();
+        let mut position = 0;
+        while let Some(offset) = text[position..].find("{{") {
+            let start = position + offset;
+            let raw = text[start..].starts_with("{{{");
+            let (open, close) = if raw { ("{{{", "}}}") } else { ("{{", "}}") };
+            let tag_start = start + open.len();
+            let end = match text[tag_start..].find(close) {
+                Some(length) => tag_start + length,
+                None => return Err(error(start, "Unclosed tag".into())),
+            };
+            let after = end + close.len();
+            let tag = text[tag_start..end].trim();
+            let sigil = match tag.chars().next() {
+                Some(c) if !raw && "#^/!".contains(c) => Some(c),
+                _ => None,
+            };
+            let (text_end, next) = match sigil {
+                Some(_) => standalone_line(text, start, after).unwrap_or((start, after)),
+                None => (start, after),
+            };
+            push_text(&mut nodes, &text[position..text_end]);
+            position = next;
+
+            let tag_name = tag[sigil.map_or(0, char::len_utf8)..].trim().to_string();
+            if tag_name.is_empty() && sigil != Some('!') {
+                return Err(error(start, "Empty tag".into()));
+            }
+            match sigil {
+                Some('#') | Some('^') => {
+                    let enclosing = std::mem::take(&mut nodes);
+                    open_sections.push((tag_name, sigil == Some('^'), enclosing, start))
+                }
+                Some('/') => match open_sections.pop() {
+                    Some((name, inverted, enclosing, _)) if name == tag_name => {
+                        let section_nodes = std::mem::replace(&mut nodes, enclosing);
+                        nodes.push(Node::Section {
+                            name,
+                            inverted,
+                            nodes: section_nodes,
+                        })
+                    }
+                    Some((name, _, _, _)) => {
+                        let message = format!("Section {} closed by {}", name, tag_name);
+                        return Err(error(start, message));
+                    }
+                    None => {
+                        let message = format!("Unexpected end of section {}", tag_name);
+                        return Err(error(start, message));
+                    }
+                },
+                Some(_) => (),
+                None => nodes.push(Node::Field {
+                    name: tag_name,
+                    raw,
+                }),
+            }
+        }
+        push_text(&mut nodes, &text[position..]);
+        match open_sections.pop() {
+            Some((name, _, _, start)) => Err(error(start, format!("Unclosed section {}", name))),
+            None => Ok(Template { nodes }),
+        }
+    }
+
+    pub fn render(&self, context: &Context) -> String {
+        let mut out = String::new();
+        render_nodes(&mut out, &self.nodes, context, &mut Vec::new());
+        out
+    }
+}
+
+/// If the tag at start..after is alone on its line, range of the line to remove with it.
+fn standalone_line(text: &str, start: usize, after: usize) -> Option<(usize, usize)> {
+    let line_start = text[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = text[after..]
+        .find('\n')
+        .map_or(text.len(), |i| after + i + 1);
+    let blank = |s: &str| s.chars().all(char::is_whitespace);
+    if blank(&text[line_start..start]) && blank(&text[after..line_end]) {
+        Some((line_start, line_end))
+    } else {
+        None
+    }
+}
+
+fn push_text(nodes: &mut Vec<Node>, text: &str) {
+    if !text.is_empty() {
+        nodes.push(Node::Text(text.to_string()))
+    }
+}
+
+/// Value of a field, from the innermost context.
+fn lookup<'v>(name: &str, root: &'v Context, stack: &[&'v Value]) -> Option<&'v Value> {
+    if name == "." {
+        return stack.last().copied();
+    }
+    let in_stack = stack.iter().rev().find_map(|value| match value {
+        Value::Map(context) => context.get(name),
+        _ => None,
+    });
+    in_stack.or_else(|| root.get(name))
+}
+
+fn render_nodes<'v>(
+    out: &mut String,
+    nodes: &[Node],
+    root: &'v Context,
+    stack: &mut Vec<&'v Value>,
+) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Field { name, raw } => {
+                if let Some(Value::Text(text)) = lookup(name, root, stack) {
+                    if *raw {
+                        out.push_str(text)
+                    } else {
+                        out.push_str(&escape(text))
+                    }
+                }
+            }
+            Node::Section {
+                name,
+                inverted,
+                nodes,
+            } => {
+                let value = lookup(name, root, stack);
+                let truthy = value.is_some_and(Value::is_truthy);
+                match value {
+                    _ if *inverted && !truthy => render_nodes(out, nodes, root, stack),
+                    _ if *inverted => (),
+                    Some(Value::List(items)) => {
+                        for item in items {
+                            stack.push(item);
+                            render_nodes(out, nodes, root, stack);
+                            stack.pop();
+                        }
+                    }
+                    Some(value) if truthy => {
+                        stack.push(value);
+                        render_nodes(out, nodes, root, stack);
+                        stack.pop();
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+}
+
+pub fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[test]
+fn rendering() {
+    let template = "\
+<h1>{{title}}</h1>
+{{! Comment }}
+{{#items}}
+  <li>{{name}}{{#tag}} [{{.}}]{{/tag}} in {{title}}</li>
+{{/items}}
+{{^items}}
+  <p>None</p>
+{{/items}}
+{{{html}}}{{missing}}
+";
+    let template = Template::parse("t.html", template).unwrap();
+    let item = |name: &str, tag: Option<&str>| Context::new().with("name", name).with("tag", tag);
+    let context = Context::new()
+        .with("title", "A & B")
+        .with("items", vec![item("x", Some("t")), item("y", None)])
+        .with("html", "<hr>");
+    let expected = "\
+<h1>A &amp; B</h1>
+  <li>x [t] in A &amp; B</li>
+  <li>y in A &amp; B</li>
+<hr>
+";
+    assert_eq!(template.render(&context), expected);
+    let empty = Context::new().with("items", Vec::<Context>::new());
+    assert_eq!(template.render(&empty), "<h1></h1>\n  <p>None</p>\n\n");
+
+    let error = |text| Template::parse("t.html", text).unwrap_err();
+    assert_eq!(error("a\n{{#b}}\n"), "t.html:2: Unclosed section b");
+    assert_eq!(error("{{#b}}{{/c}}"), "t.html:1: Section b closed by c");
+    assert_eq!(error("{{/b}}"), "t.html:1: Unexpected end of section b");
+    assert_eq!(error("\n{{b"), "t.html:2: Unclosed tag");
+}
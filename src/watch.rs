@@ -0,0 +1,130 @@
+use inotify::{Inotify, WatchDescriptor, WatchMask};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/******************************************************************************
+ * File change notifications, using inotify.
+ *
+ * Parent directories of files are watched instead of the files themselves,
+ * as editors often save by writing a new file and renaming it over the old one.
+ * Paths are compared after normalization, as the same file can be named by relative or absolute paths.
+ */
+
+/// Delay to group events following a change, as saving a file often generates several.
+const SETTLE_DELAY: Duration = Duration::from_millis(100);
+
+pub struct FileWatcher {
+    inotify: Inotify,
+    directories: HashMap<WatchDescriptor, PathBuf>,
+    buffer: Vec<u8>,
+}
+
+/// Absolute path with a canonical parent directory. The file itself may not exist.
+pub fn normalize(path: &Path) -> PathBuf {
+    let parent = match path.parent() {
+        Some(parent) if parent != Path::new("") => parent,
+        _ => Path::new("."),
+    };
+    match (parent.canonicalize(), path.file_name()) {
+        (Ok(parent), Some(name)) => parent.join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+impl FileWatcher {
+    /// Watch the directories containing the given files.
+    pub fn new(files: &[PathBuf]) -> io::Result<FileWatcher> {
+        let mut watcher = FileWatcher {
+            inotify: Inotify::init()?,
+            directories: HashMap::new(),
+            buffer: vec![0; 4096],
+        };
+        watcher.watch(files)?;
+        Ok(watcher)
+    }
+
+    /// Watch the directories containing the given files instead of the current ones.
+    /// Directories watched before and after keep their pending events.
+    pub fn watch(&mut self, files: &[PathBuf]) -> io::Result<()> {
+        let directories: Vec<PathBuf> = files
+            .iter()
+            .map(|file| {
+                normalize(file)
+                    .parent()
+                    .map_or_else(|| PathBuf::from("/"), Path::to_path_buf)
+            })
+            .collect();
+        let removed: Vec<WatchDescriptor> = (self.directories.iter())
+            .filter(|(_, directory)| !directories.contains(directory))
+            .map(|(descriptor, _)| descriptor.clone())
+            .collect();
+        for descriptor in removed {
+            self.directories.remove(&descriptor);
+            // Fails if the directory was deleted, which already removed the watch
+            let _ = self.inotify.rm_watch(descriptor);
+        }
+        let mask = WatchMask::CLOSE_WRITE
+            | WatchMask::MODIFY
+            | WatchMask::CREATE
+            | WatchMask::DELETE
+            | WatchMask::MOVED_TO
+            | WatchMask::MOVED_FROM;
+        for directory in directories {
+            if !self.directories.values().any(|d| *d == directory) {
+                let descriptor = self.inotify.add_watch(&directory, mask)?;
+                self.directories.insert(descriptor, directory);
+            }
+        }
+        Ok(())
+    }
+
+    /// Block until files change in the watched directories, and return their normalized paths.
+    pub fn wait(&mut self) -> io::Result<Vec<PathBuf>> {
+        let mut changed = Vec::new();
+        let events = self.inotify.read_events_blocking(&mut self.buffer)?;
+        Self::collect(&self.directories, events, &mut changed);
+        thread::sleep(SETTLE_DELAY);
+        let events = self.inotify.read_events(&mut self.buffer)?;
+        Self::collect(&self.directories, events, &mut changed);
+        Ok(changed)
+    }
+
+    fn collect(
+        directories: &HashMap<WatchDescriptor, PathBuf>,
+        events: inotify::Events,
+        changed: &mut Vec<PathBuf>,
+    ) {
+        for event in events {
+            if let (Some(directory), Some(name)) = (directories.get(&event.wd), event.name) {
+                let path = directory.join(name);
+                if !changed.contains(&path) {
+                    changed.push(path)
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn file_changes() {
+    let directory = std::env::temp_dir().join(format!("wimd-watch-test-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let file = directory.join("notes.md");
+    std::fs::write(&file, "a").unwrap();
+    let mut watcher = FileWatcher::new(std::slice::from_ref(&file)).unwrap();
+    std::fs::write(&file, "b").unwrap();
+    assert_eq!(watcher.wait().unwrap(), vec![normalize(&file)]);
+
+    // Changes made before waiting are reported, and watches follow the file list
+    let sub_directory = directory.join("sub");
+    std::fs::create_dir_all(&sub_directory).unwrap();
+    let other = sub_directory.join("other.md");
+    watcher.watch(std::slice::from_ref(&other)).unwrap();
+    std::fs::write(&file, "c").unwrap();
+    std::fs::write(&other, "a").unwrap();
+    assert_eq!(watcher.wait().unwrap(), vec![normalize(&other)]);
+    std::fs::remove_dir_all(&directory).unwrap();
+}
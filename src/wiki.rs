@@ -0,0 +1,945 @@
+use crate::ast::*;
+use crate::code::CodeData;
+use crate::config::Config;
+use crate::document::{
+    for_each_text_unit, CategoryIndex, IndexedDocument, KeywordIndex, TextUnitKind,
+};
+use crate::graph::{CooccurrenceGraph, Scope};
+use crate::search::{search_index_script, SEARCH_SCRIPT};
+use crate::template::{escape, Context, Template};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Write};
+use std::fs;
+use std::io;
+use std::ops::Range;
+
+/******************************************************************************
+ * Html wiki generation.
+ *
+ * The wiki is a directory of static html pages:
+ * - index.html: the whole document, with keyword occurrences linked to keyword pages.
+ * - keywords.html: list of categories, and alphabetical list of keywords.
+ * - category_<slug>.html: for each category, alphabetical list of its keywords.
+ * - keyword_<slug>.html: for each keyword, related keywords, referencing sections and text units where it occurs.
+ * Page names use slugs of names, so they stay the same when other keywords are added or removed.
+ * Pages of keywords or categories which no longer exist are removed from the output directory.
+ *
+ * Section titles in index.html have an id from the section slug, used by links from keyword pages.
+ *
+ * A single self-contained printable page can also be rendered, with the document followed by a glossary of keywords.
+ * Links are anchors in the page, and the style sheet is included in the page.
+ * Pages can also be rendered for an EPUB, without search; the theme templates must then produce well formed XHTML.
+ * The document and keyword pages start with a collapsible table of contents.
+ *
+ * - search-index.js, search.js: search index and script for the search box in the navigation bar of each page.
+ * - style.css: shared style sheet, with a colour for each keyword category.
+ *   Category colours are taken from the configuration, or generated.
+ *
+ * Pages are rendered with the templates of a theme (see the template module for the syntax).
+ * The built-in theme is in src/theme, and a theme directory may replace any of its files:
+ * - layout.html: whole page. Fields: language (optional), title, document_title, content (html).
+ * - document.html: content of index.html. Fields: header (true if any of title, byline or tags),
+ *   title (optional), byline, tags, toc (html), content (html of top level blocks and sections).
+ * - section.html: section of the document. Fields: level, anchor, title (html), content (html of blocks and sub sections).
+ * - keywords.html: content of keywords.html. Fields: has_categories, categories, keywords.
+ * - category.html: content of a category page. Fields of a category, with keywords.
+ * - keyword.html: content of a keyword page. Fields of a keyword, with category (optional), toc (html),
+ *   has_related, related (keywords), referenced_in (sections),
+ *   occurrences (fields of a section, with anchor and units: class and content in html).
+ * - print.html: whole printable page. Fields: language (optional), title, style (css of style.css and category colours),
+ *   document (html of the content of document.html), glossary (alphabetical keywords, with fields of keyword.html and anchor).
+ * - epub_layout.html: whole page in an EPUB, as XHTML, with the same fields as layout.html.
+ * - style.css: base style sheet.
+ * Keywords have fields name, page and classes; categories have name, page and index; sections have name and page.
+ *
+ * Pages are built in memory as strings; writing to a String cannot fail.
+ */
+
+/// Maximum number of related keywords listed on a keyword page.
+pub const MAX_RELATED_KEYWORDS: usize = 10;
+
+/// Templates and style of the wiki, from a theme directory or built in.
+#[derive(Clone)]
+pub struct Theme {
+    layout: Template,
+    epub_layout: Template,
+    document: Template,
+    section: Template,
+    keywords: Template,
+    category: Template,
+    keyword: Template,
+    print: Template,
+    style: String,
+}
+
+impl Theme {
+    /// Load the configured theme. Files missing from the theme directory are taken from the built-in theme.
+    pub fn load(config: &Config) -> Result<Theme, String> {
+        if let Some(directory) = &config.theme {
+            if !directory.is_dir() {
+                return Err(format!(
+                    "{}: Theme directory not found",
+                    directory.display()
+                ));
+            }
+        }
+        // File content, and name used in errors
+        let file = |name: &str, built_in: &str| match &config.theme {
+            Some(directory) if directory.join(name).exists() => {
+                let path = directory.join(name);
+                match fs::read_to_string(&path) {
+                    Ok(text) => Ok((text, path.display().to_string())),
+                    Err(e) => Err(format!("{}: {}", path.display(), e)),
+                }
+            }
+            _ => Ok((built_in.to_string(), name.to_string())),
+        };
+        let template = |name: &str, built_in: &str| {
+            let (text, name) = file(name, built_in)?;
+            Template::parse(&name, &text)
+        };
+        Ok(Theme {
+            layout: template("layout.html", include_str!("theme/layout.html"))?,
+            epub_layout: template("epub_layout.html", include_str!("theme/epub_layout.html"))?,
+            document: template("document.html", include_str!("theme/document.html"))?,
+            section: template("section.html", include_str!("theme/section.html"))?,
+            keywords: template("keywords.html", include_str!("theme/keywords.html"))?,
+            category: template("category.html", include_str!("theme/category.html"))?,
+            keyword: template("keyword.html", include_str!("theme/keyword.html"))?,
+            print: template("print.html", include_str!("theme/print.html"))?,
+            style: file("style.css", include_str!("theme/style.css"))?.0,
+        })
+    }
+}
+
+/// Generate wiki pages in the configured output directory, creating it if needed.
+/// If changed keywords are given, only pages of these keywords and their related keywords are rendered again,
+/// with pages listing keywords and missing pages. Otherwise all pages are rendered.
+/// Keyword and category pages of a previous build which are not pages of this one are removed.
+pub fn generate(
+    document: &IndexedDocument,
+    config: &Config,
+    theme: &Theme,
+    changed_keywords: Option<&BTreeSet<KeywordIndex>>,
+) -> io::Result<()> {
+    let output_dir = config.output.as_path();
+    fs::create_dir_all(output_dir)?;
+    let graph = CooccurrenceGraph::from(document, Scope::Section);
+    // Related keywords of changed keywords may have changed
+    let to_render = changed_keywords.map(|changed| {
+        let mut to_render = changed.clone();
+        for (a, b) in graph.edges.keys() {
+            if changed.contains(a) || changed.contains(b) {
+                to_render.insert(*a);
+                to_render.insert(*b);
+            }
+        }
+        to_render
+    });
+    let render_keyword = |keyword: KeywordIndex| match &to_render {
+        Some(to_render) => {
+            to_render.contains(&keyword)
+                || !output_dir
+                    .join(keyword_page_name(document, keyword))
+                    .exists()
+        }
+        None => true,
+    };
+    render(
+        document,
+        config,
+        theme,
+        &graph,
+        &render_keyword,
+        &mut |name, page| fs::write(output_dir.join(name), page),
+    )?;
+    let keyword_pages = (0..document.keywords().len()).map(|k| keyword_page_name(document, k));
+    let category_pages = (0..document.categories().len()).map(|c| category_page_name(document, c));
+    let pages: BTreeSet<String> = keyword_pages.chain(category_pages).collect();
+    for entry in fs::read_dir(output_dir)? {
+        let name = entry?.file_name();
+        let name = name.to_string_lossy();
+        if is_indexed_page_name(&name) && !pages.contains(name.as_ref()) {
+            fs::remove_file(output_dir.join(name.as_ref()))?
+        }
+    }
+    Ok(())
+}
+
+/// Render all wiki pages in memory, by file name.
+pub fn render_pages(
+    document: &IndexedDocument,
+    config: &Config,
+    theme: &Theme,
+) -> io::Result<BTreeMap<String, String>> {
+    let graph = CooccurrenceGraph::from(document, Scope::Section);
+    let mut pages = BTreeMap::new();
+    render(
+        document,
+        config,
+        theme,
+        &graph,
+        &|_| true,
+        &mut |name, page| {
+            pages.insert(name.to_string(), page);
+            Ok(())
+        },
+    )?;
+    Ok(pages)
+}
+
+/// Render pages and give them to the output function with their file name.
+/// Keyword pages are only rendered if selected.
+fn render<O>(
+    document: &IndexedDocument,
+    config: &Config,
+    theme: &Theme,
+    graph: &CooccurrenceGraph,
+    render_keyword: &dyn Fn(KeywordIndex) -> bool,
+    output: &mut O,
+) -> io::Result<()>
+where
+    O: FnMut(&str, String) -> io::Result<()>,
+{
+    output("style.css", style_sheet(document, config, theme))?;
+    output("index.html", document_page(document, theme))?;
+    output("keywords.html", keyword_list_page(document, theme))?;
+    let section_page = |path: &[usize]| Links::Pages.section(document, path);
+    let index = search_index_script(document, &|k| keyword_page_name(document, k), &section_page);
+    output("search-index.js", index)?;
+    output("search.js", SEARCH_SCRIPT.to_string())?;
+    for keyword in 0..document.keywords().len() {
+        if render_keyword(keyword) {
+            output(
+                &keyword_page_name(document, keyword),
+                keyword_page(document, theme, graph, keyword),
+            )?;
+        }
+    }
+    for category in 0..document.categories().len() {
+        output(
+            &category_page_name(document, category),
+            category_page(document, theme, category),
+        )?;
+    }
+    Ok(())
+}
+
+/// Pages for an EPUB by file name, in reading order: style sheet, document, keyword list, keywords and categories.
+pub fn render_epub_pages(
+    document: &IndexedDocument,
+    config: &Config,
+    theme: &Theme,
+) -> Vec<(String, String)> {
+    let theme = Theme {
+        layout: theme.epub_layout.clone(),
+        ..theme.clone()
+    };
+    let graph = CooccurrenceGraph::from(document, Scope::Section);
+    let mut pages = Vec::new();
+    let mut output = |name: &str, page: String| {
+        if !name.ends_with(".js") {
+            pages.push((name.to_string(), page))
+        }
+        Ok(())
+    };
+    render(document, config, &theme, &graph, &|_| true, &mut output).unwrap();
+    pages
+}
+
+/// Single printable page, with the document followed by an alphabetical glossary of keywords.
+pub fn render_single_page(document: &IndexedDocument, config: &Config, theme: &Theme) -> String {
+    let graph = CooccurrenceGraph::from(document, Scope::Section);
+    let keywords = sorted_keywords(document, (0..document.keywords().len()).collect());
+    let glossary: Vec<Context> = (keywords.into_iter())
+        .map(|keyword| {
+            let anchor = keyword_anchor(keyword);
+            let prefix = format!("{}_", anchor);
+            let (fields, _) = keyword_fields(document, &graph, Links::Anchors, keyword, &prefix);
+            fields.with("anchor", anchor)
+        })
+        .collect();
+    let context = Context::new()
+        .with(
+            "language",
+            document.document().front_matter.language.as_deref(),
+        )
+        .with("title", document_title(document))
+        .with("style", style_sheet(document, config, theme))
+        .with(
+            "document",
+            document_content(document, theme, Links::Anchors),
+        )
+        .with("glossary", glossary);
+    theme.print.render(&context)
+}
+
+/// Page names from keyword and category slugs, which never contain '_', so they cannot conflict with other pages.
+pub fn keyword_page_name(document: &IndexedDocument, keyword: KeywordIndex) -> String {
+    format!("keyword_{}.html", document.keyword_slug(keyword))
+}
+fn category_page_name(document: &IndexedDocument, category: CategoryIndex) -> String {
+    format!("category_{}.html", document.category_slug(category))
+}
+
+/// Test if a file name is a keyword or category page name, which may be left from a previous build.
+fn is_indexed_page_name(name: &str) -> bool {
+    (name.starts_with("keyword_") || name.starts_with("category_")) && name.ends_with(".html")
+}
+
+/// Anchor of a keyword entry in the printable page.
+/// Section slugs never contain '_', so they cannot conflict.
+fn keyword_anchor(keyword: KeywordIndex) -> String {
+    format!("keyword_{}", keyword)
+}
+
+/// Link targets: separate pages of the wiki, or anchors in the printable page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Links {
+    Pages,
+    Anchors,
+}
+
+impl Links {
+    fn keyword(self, document: &IndexedDocument, keyword: KeywordIndex) -> String {
+        match self {
+            Links::Pages => keyword_page_name(document, keyword),
+            Links::Anchors => format!("#{}", keyword_anchor(keyword)),
+        }
+    }
+    /// None for the printable page, without category lists.
+    fn category(self, document: &IndexedDocument, category: CategoryIndex) -> Option<String> {
+        match self {
+            Links::Pages => Some(category_page_name(document, category)),
+            Links::Anchors => None,
+        }
+    }
+    fn section(self, document: &IndexedDocument, path: &[usize]) -> String {
+        match (self, document.section_slug(path)) {
+            (Links::Pages, Some(anchor)) => format!("index.html#{}", anchor),
+            (Links::Pages, None) => "index.html".to_string(),
+            (Links::Anchors, Some(anchor)) => format!("#{}", anchor),
+            (Links::Anchors, None) => "#document".to_string(),
+        }
+    }
+}
+
+/// Theme style with category colours.
+/// Generated hues are spread using the golden angle to stay distinct.
+fn style_sheet(document: &IndexedDocument, config: &Config, theme: &Theme) -> String {
+    let mut style = theme.style.clone();
+    for category in 0..document.categories().len() {
+        let settings = config.category_settings(document.category(category));
+        let colour = match settings.and_then(|s| s.colour.as_ref()) {
+            Some(colour) => colour.clone(),
+            None => format!("hsl({:.0}, 60%, 35%)", (category as f64 * 137.5) % 360.),
+        };
+        writeln!(
+            &mut style,
+            "a.category-{} {{ color: {}; }}",
+            category, colour
+        )
+        .unwrap();
+    }
+    style
+}
+
+/// Html classes for links to a keyword.
+fn keyword_classes(document: &IndexedDocument, keyword: KeywordIndex) -> String {
+    match document.keyword_category(keyword) {
+        Some(category) => format!("keyword category-{}", category),
+        None => "keyword".to_string(),
+    }
+}
+
+/// Sort keywords alphabetically, case insensitive.
+pub fn sorted_keywords(
+    document: &IndexedDocument,
+    mut keywords: Vec<KeywordIndex>,
+) -> Vec<KeywordIndex> {
+    keywords.sort_by_cached_key(|k| document.keyword(*k).to_lowercase());
+    keywords
+}
+
+/// Title of the document, from front matter.
+pub fn document_title(document: &IndexedDocument) -> &str {
+    let title = document.document().front_matter.title.as_ref();
+    title.map_or("Document", String::as_str)
+}
+
+fn page(document: &IndexedDocument, theme: &Theme, title: &str, content: String) -> String {
+    let context = Context::new()
+        .with(
+            "language",
+            document.document().front_matter.language.as_deref(),
+        )
+        .with("title", title)
+        .with("document_title", document_title(document))
+        .with("content", content);
+    theme.layout.render(&context)
+}
+
+/// Page with the whole document.
+fn document_page(document: &IndexedDocument, theme: &Theme) -> String {
+    let content = document_content(document, theme, Links::Pages);
+    page(document, theme, document_title(document), content)
+}
+
+/// The whole document, and front matter metadata in the header.
+fn document_content(document: &IndexedDocument, theme: &Theme, links: Links) -> String {
+    let front_matter = &document.document().front_matter;
+    let byline: Vec<&str> = [&front_matter.author, &front_matter.date]
+        .iter()
+        .filter_map(|field| field.as_deref())
+        .collect();
+    let header =
+        front_matter.title.is_some() || !byline.is_empty() || !front_matter.tags.is_empty();
+    let mut toc = Vec::new();
+    for_each_text_unit(document.document(), &mut |path, kind, inlines| {
+        if let TextUnitKind::Title(level) = kind {
+            toc.push(TocEntry {
+                level,
+                anchor: document.section_slug(path).unwrap().to_string(),
+                title: escape(&inlines[0].string),
+            })
+        }
+    });
+    let mut toc_html = String::new();
+    write_toc(&mut toc_html, &toc).unwrap();
+    let mut content = String::new();
+    let root = &document.document().content;
+    write_section_content(&mut content, document, theme, links, root, &mut Vec::new()).unwrap();
+    let context = Context::new()
+        .with("header", header)
+        .with("title", front_matter.title.as_deref())
+        .with("byline", byline.join(", "))
+        .with("tags", front_matter.tags.join(", "))
+        .with("toc", toc_html)
+        .with("content", content);
+    theme.document.render(&context)
+}
+
+/// Categories, and alphabetical list of keywords.
+fn keyword_list_page(document: &IndexedDocument, theme: &Theme) -> String {
+    let categories: Vec<Context> = (0..document.categories().len())
+        .map(|category| category_context(document, Links::Pages, category))
+        .collect();
+    let keywords = sorted_keywords(document, (0..document.keywords().len()).collect());
+    let context = Context::new()
+        .with("has_categories", !categories.is_empty())
+        .with("categories", categories)
+        .with(
+            "keywords",
+            keyword_contexts(document, Links::Pages, &keywords),
+        );
+    let content = theme.keywords.render(&context);
+    page(document, theme, "Keywords", content)
+}
+
+/// Alphabetical list of keywords of a category.
+fn category_page(document: &IndexedDocument, theme: &Theme, category: CategoryIndex) -> String {
+    let keywords = sorted_keywords(document, document.category_keywords(category));
+    let context = category_context(document, Links::Pages, category).with(
+        "keywords",
+        keyword_contexts(document, Links::Pages, &keywords),
+    );
+    let content = theme.category.render(&context);
+    page(document, theme, document.category(category), content)
+}
+
+/// Page for one keyword, with a table of contents.
+fn keyword_page(
+    document: &IndexedDocument,
+    theme: &Theme,
+    graph: &CooccurrenceGraph,
+    keyword: KeywordIndex,
+) -> String {
+    let (context, toc) = keyword_fields(document, graph, Links::Pages, keyword, "");
+    let mut toc_html = String::new();
+    write_toc(&mut toc_html, &toc).unwrap();
+    let content = theme.keyword.render(&context.with("toc", toc_html));
+    page(document, theme, document.keyword(keyword), content)
+}
+
+/// Fields of a keyword for keyword pages and glossary entries: related keywords, and text units where it occurs grouped by section.
+/// Also returns table of contents entries for headings of the keyword template.
+/// Anchors of occurrence headings are prefixed, to be unique in the printable page.
+fn keyword_fields(
+    document: &IndexedDocument,
+    graph: &CooccurrenceGraph,
+    links: Links,
+    keyword: KeywordIndex,
+    anchor_prefix: &str,
+) -> (Context, Vec<TocEntry>) {
+    let mut toc = Vec::new();
+    let mut toc_entry = |level: usize, anchor: &str, title: &str| {
+        toc.push(TocEntry {
+            level,
+            anchor: anchor.to_string(),
+            title: escape(title),
+        })
+    };
+
+    let related: Vec<KeywordIndex> = (graph.related_keywords(keyword).into_iter())
+        .take(MAX_RELATED_KEYWORDS)
+        .map(|(other, _)| other)
+        .collect();
+    if !related.is_empty() {
+        toc_entry(1, "related", "Related keywords")
+    }
+    toc_entry(1, "referenced-in", "Referenced in");
+    let referenced_in: Vec<Context> = (document.referencing_sections(keyword).iter())
+        .map(|path| section_context(document, links, path))
+        .collect();
+
+    let explicit = document.explicit_occurrences(keyword).iter();
+    let implicit = document.implicit_occurrences(keyword).iter();
+    let units: BTreeSet<usize> = explicit
+        .chain(implicit)
+        .map(|inline| document.inline_location(*inline).unit)
+        .collect();
+    toc_entry(1, "occurrences", "Occurrences");
+    // Text units grouped by section: section path, anchor, unit contexts
+    let mut occurrences: Vec<(Vec<usize>, String, Vec<Context>)> = Vec::new();
+    let mut unit = 0;
+    for_each_text_unit(document.document(), &mut |path, kind, inlines| {
+        if units.contains(&unit) {
+            if occurrences.last().map(|(p, _, _)| p.as_slice()) != Some(path) {
+                let anchor = format!(
+                    "{}occurrences-{}",
+                    anchor_prefix,
+                    document.section_slug(path).unwrap_or("top")
+                );
+                toc_entry(2, &anchor, &document.section_display_name(path));
+                occurrences.push((path.to_vec(), anchor, Vec::new()))
+            }
+            let class = match kind {
+                TextUnitKind::Title(_) => "title",
+                TextUnitKind::Paragraph => "paragraph",
+                TextUnitKind::ListItem { .. } => "list-item",
+                TextUnitKind::TableCell { .. } => "table-cell",
+            };
+            let mut content = String::new();
+            write_inlines(&mut content, document, links, inlines).unwrap();
+            let unit_context = Context::new().with("class", class).with("content", content);
+            occurrences.last_mut().unwrap().2.push(unit_context)
+        }
+        unit += 1
+    });
+    let occurrences: Vec<Context> = (occurrences.into_iter())
+        .map(|(path, anchor, units)| {
+            section_context(document, links, &path)
+                .with("anchor", anchor)
+                .with("units", units)
+        })
+        .collect();
+
+    let category = document.keyword_category(keyword);
+    let context = keyword_context(document, links, keyword)
+        .with(
+            "category",
+            category.map(|c| category_context(document, links, c)),
+        )
+        .with("data", data_contexts(document.keyword_data(keyword)))
+        .with("has_related", !related.is_empty())
+        .with("related", keyword_contexts(document, links, &related))
+        .with("referenced_in", referenced_in)
+        .with("occurrences", occurrences);
+    (context, toc)
+}
+
+/// Interpreted code blocks, as tables of fields.
+fn data_contexts(data: &[CodeData]) -> Vec<Context> {
+    (data.iter())
+        .map(|data| {
+            let fields: Vec<Context> = (data.fields.iter())
+                .map(|(key, value)| {
+                    Context::new()
+                        .with("key", key.as_str())
+                        .with("value", value.as_str())
+                })
+                .collect();
+            Context::new()
+                .with("lang", data.lang.as_str())
+                .with("fields", fields)
+        })
+        .collect()
+}
+
+/// Entry of a table of contents, with level from 1 and html title.
+struct TocEntry {
+    level: usize,
+    anchor: String,
+    title: String,
+}
+
+/// Collapsible table of contents. Entries with sub entries are also collapsible.
+fn write_toc<W: Write>(out: &mut W, entries: &[TocEntry]) -> fmt::Result {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    writeln!(
+        out,
+        "<details class=\"toc\" open=\"open\">\n<summary>Contents</summary>"
+    )?;
+    write_toc_entries(out, entries)?;
+    writeln!(out, "</details>")
+}
+fn write_toc_entries<W: Write>(out: &mut W, entries: &[TocEntry]) -> fmt::Result {
+    writeln!(out, "<ul>")?;
+    let mut i = 0;
+    while i < entries.len() {
+        let entry = &entries[i];
+        let end = entries[i + 1..]
+            .iter()
+            .position(|e| e.level <= entry.level)
+            .map_or(entries.len(), |n| i + 1 + n);
+        let link = format!(r##"<a href="#{}">{}</a>"##, entry.anchor, entry.title);
+        if end > i + 1 {
+            writeln!(
+                out,
+                "<li><details open=\"open\"><summary>{}</summary>",
+                link
+            )?;
+            write_toc_entries(out, &entries[i + 1..end])?;
+            writeln!(out, "</details></li>")?
+        } else {
+            writeln!(out, "<li>{}</li>", link)?
+        }
+        i = end
+    }
+    writeln!(out, "</ul>")
+}
+
+fn keyword_context(document: &IndexedDocument, links: Links, keyword: KeywordIndex) -> Context {
+    Context::new()
+        .with("name", document.keyword(keyword))
+        .with("page", links.keyword(document, keyword))
+        .with("classes", keyword_classes(document, keyword))
+}
+fn keyword_contexts(
+    document: &IndexedDocument,
+    links: Links,
+    keywords: &[KeywordIndex],
+) -> Vec<Context> {
+    let contexts = keywords.iter();
+    contexts
+        .map(|k| keyword_context(document, links, *k))
+        .collect()
+}
+
+fn category_context(document: &IndexedDocument, links: Links, category: CategoryIndex) -> Context {
+    Context::new()
+        .with("name", document.category(category))
+        .with("page", links.category(document, category))
+        .with("index", category)
+}
+
+fn section_context(document: &IndexedDocument, links: Links, path: &[usize]) -> Context {
+    Context::new()
+        .with("name", document.section_display_name(path))
+        .with("page", links.section(document, path))
+}
+
+/// Write blocks and sub sections of the section at the given index path.
+fn write_section_content<W: Write>(
+    out: &mut W,
+    document: &IndexedDocument,
+    theme: &Theme,
+    links: Links,
+    content: &SectionContent,
+    path: &mut Vec<usize>,
+) -> fmt::Result {
+    write_blocks(out, document, links, &content.blocks)?;
+    for (i, section) in content.sub_sections.iter().enumerate() {
+        path.push(i);
+        let mut title = String::new();
+        write_inline(&mut title, document, links, &section.title)?;
+        let mut section_content = String::new();
+        write_section_content(
+            &mut section_content,
+            document,
+            theme,
+            links,
+            &section.content,
+            path,
+        )?;
+        let context = Context::new()
+            .with("level", path.len())
+            .with("anchor", document.section_slug(path).unwrap())
+            .with("title", title)
+            .with("content", section_content);
+        out.write_str(&theme.section.render(&context))?;
+        path.pop();
+    }
+    Ok(())
+}
+
+fn write_blocks<W: Write>(
+    out: &mut W,
+    document: &IndexedDocument,
+    links: Links,
+    blocks: &[BlockElement],
+) -> fmt::Result {
+    for block in blocks {
+        match block {
+            BlockElement::Paragraph(inlines) => {
+                out.write_str("<p>")?;
+                write_inlines(out, document, links, inlines)?;
+                out.write_str("</p>\n")?
+            }
+            BlockElement::Rule => out.write_str("<hr/>\n")?,
+            BlockElement::List(list) => write_list(out, document, links, list)?,
+            BlockElement::Code { lang, text, .. } => {
+                match lang {
+                    Some(lang) => write!(out, "<pre><code class=\"language-{}\">", escape(lang))?,
+                    None => out.write_str("<pre><code>")?,
+                }
+                out.write_str(&escape(text))?;
+                out.write_str("</code></pre>\n")?
+            }
+            BlockElement::Quote(blocks) => {
+                out.write_str("<blockquote>\n")?;
+                write_blocks(out, document, links, blocks)?;
+                out.write_str("</blockquote>\n")?
+            }
+            BlockElement::Table(table) => {
+                out.write_str("<table>\n<thead>\n")?;
+                write_table_row(out, document, links, table, &table.header, "th")?;
+                out.write_str("</thead>\n<tbody>\n")?;
+                for row in &table.rows {
+                    write_table_row(out, document, links, table, row, "td")?
+                }
+                out.write_str("</tbody>\n</table>\n")?
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_table_row<W: Write>(
+    out: &mut W,
+    document: &IndexedDocument,
+    links: Links,
+    table: &Table,
+    cells: &[TableCell],
+    tag: &str,
+) -> fmt::Result {
+    out.write_str("<tr>")?;
+    for (cell, alignment) in cells.iter().zip(&table.alignments) {
+        let style = match alignment {
+            Alignment::None => "",
+            Alignment::Left => " style=\"text-align: left\"",
+            Alignment::Center => " style=\"text-align: center\"",
+            Alignment::Right => " style=\"text-align: right\"",
+        };
+        write!(out, "<{}{}>", tag, style)?;
+        write_inlines(out, document, links, cell)?;
+        write!(out, "</{}>", tag)?
+    }
+    out.write_str("</tr>\n")
+}
+
+fn write_list<W: Write>(
+    out: &mut W,
+    document: &IndexedDocument,
+    links: Links,
+    list: &List,
+) -> fmt::Result {
+    let tag = if list.ordered { "ol" } else { "ul" };
+    writeln!(out, "<{}>", tag)?;
+    for item in &list.items {
+        out.write_str("<li>")?;
+        write_inlines(out, document, links, &item.text_content)?;
+        if let Some(sub_list) = &item.sub_list {
+            out.write_char('\n')?;
+            write_list(out, document, links, sub_list)?
+        }
+        out.write_str("</li>\n")?
+    }
+    writeln!(out, "</{}>", tag)
+}
+
+/// Write inlines of a text unit, separated by line breaks from the source.
+fn write_inlines<W: Write>(
+    out: &mut W,
+    document: &IndexedDocument,
+    links: Links,
+    inlines: &[InlineElement],
+) -> fmt::Result {
+    for (i, inline) in inlines.iter().enumerate() {
+        if i > 0 {
+            out.write_char('\n')?
+        }
+        write_inline(out, document, links, inline)?
+    }
+    Ok(())
+}
+
+/// Write inline text with keyword links and highlighting.
+/// Keyword ranges do not overlap each other, but may overlap highlight ranges.
+/// Highlights are thus split at keyword link boundaries to produce valid html.
+fn write_inline<W: Write>(
+    out: &mut W,
+    document: &IndexedDocument,
+    links: Links,
+    inline: &InlineElement,
+) -> fmt::Result {
+    let string = &inline.string;
+    let highlights: Vec<&Range<usize>> = inline
+        .tags
+        .iter()
+        .filter_map(|(r, tag)| match tag {
+            InlineTag::Highlight => Some(r),
+            _ => None,
+        })
+        .collect();
+    let mut position = 0;
+    for (range, tag) in &inline.tags {
+        let (class, keyword) = match tag {
+            InlineTag::ExplicitKeyword(k) => ("explicit", *k),
+            InlineTag::ImplicitKeyword(k) => ("implicit", *k),
+            InlineTag::Highlight | InlineTag::Secret(_) => continue,
+        };
+        write_highlighted(out, string, position..range.start, &highlights)?;
+        write!(
+            out,
+            r#"<a class="{} {}" href="{}">"#,
+            keyword_classes(document, keyword),
+            class,
+            links.keyword(document, keyword)
+        )?;
+        write_highlighted(out, string, range.clone(), &highlights)?;
+        out.write_str("</a>")?;
+        position = range.end
+    }
+    write_highlighted(out, string, position..string.len(), &highlights)
+}
+
+/// Write a range of text, with highlight ranges (sorted, non overlapping) clipped to it.
+fn write_highlighted<W: Write>(
+    out: &mut W,
+    string: &str,
+    range: Range<usize>,
+    highlights: &[&Range<usize>],
+) -> fmt::Result {
+    let mut position = range.start;
+    for highlight in highlights {
+        let start = highlight.start.max(range.start);
+        let end = highlight.end.min(range.end);
+        if start < end {
+            out.write_str(&escape(&string[position..start]))?;
+            write!(out, "<strong>{}</strong>", escape(&string[start..end]))?;
+            position = end
+        }
+    }
+    out.write_str(&escape(&string[position..range.end]))
+}
+
+#[test]
+fn inline_rendering() {
+    let (document, keywords) = parse("**a *b* c** b\n").unwrap();
+    let document = IndexedDocument::from(document, keywords);
+    let mut out = String::new();
+    let theme = Theme::load(&Config::default_in(".".into())).unwrap();
+    write_section_content(
+        &mut out,
+        &document,
+        &theme,
+        Links::Pages,
+        &document.document().content,
+        &mut Vec::new(),
+    )
+    .unwrap();
+    assert_eq!(
+        out,
+        concat!(
+            r#"<p><strong>a </strong><a class="keyword explicit" href="keyword_b.html"><strong>b</strong></a>"#,
+            r#"<strong> c</strong> <a class="keyword implicit" href="keyword_b.html">b</a></p>"#,
+            "\n"
+        )
+    );
+}
+
+#[test]
+fn table_of_contents() {
+    let entry = |level, anchor: &str| TocEntry {
+        level,
+        anchor: anchor.into(),
+        title: anchor.to_uppercase(),
+    };
+    let mut out = String::new();
+    write_toc(&mut out, &[entry(1, "a"), entry(2, "b"), entry(1, "c")]).unwrap();
+    let expected = r##"<details class="toc" open="open">
+<summary>Contents</summary>
+<ul>
+<li><details open="open"><summary><a href="#a">A</a></summary>
+<ul>
+<li><a href="#b">B</a></li>
+</ul>
+</details></li>
+<li><a href="#c">C</a></li>
+</ul>
+</details>
+"##;
+    assert_eq!(out, expected);
+}
+
+#[test]
+fn single_page() {
+    let (document, keywords) = parse("# Lieux #\nLa *Tour*.\n").unwrap();
+    let document = IndexedDocument::from(document, keywords);
+    let config = Config::default_in(".".into());
+    let theme = Theme::load(&config).unwrap();
+    let page = render_single_page(&document, &config, &theme);
+    assert!(page.contains(r##"<a class="keyword explicit" href="#keyword_0">Tour</a>"##));
+    assert!(page.contains(r#"<article class="glossary-entry" id="keyword_0">"#));
+    assert!(page.contains(r##"<li><a href="#lieux">Lieux</a></li>"##));
+    assert!(!page.contains(".html") && !page.contains("<script") && !page.contains("<link"));
+}
+
+#[test]
+fn stale_pages() {
+    let root = std::env::temp_dir().join(format!("wimd-wiki-test-{}", std::process::id()));
+    let config = Config::default_in(root.clone());
+    let theme = Theme::load(&config).unwrap();
+    let generate_text = |text: &str, changed: Option<&BTreeSet<KeywordIndex>>| {
+        let (document, keywords) = parse(text).unwrap();
+        let document = IndexedDocument::from(document, keywords);
+        generate(&document, &config, &theme, changed).unwrap();
+    };
+    let pages = || {
+        let names = fs::read_dir(&config.output).unwrap();
+        let names = names.map(|entry| entry.unwrap().file_name().into_string().unwrap());
+        let mut names: Vec<String> = names.filter(|name| name.ends_with(".html")).collect();
+        names.sort();
+        names
+    };
+    generate_text("# Lieux #\n*Tour* : haute.\n\n*Général* et *Mira*\n", None);
+    fs::write(config.output.join("notes.html"), "").unwrap();
+    assert_eq!(
+        pages(),
+        vec![
+            "category_lieux.html",
+            "index.html",
+            "keyword_general.html",
+            "keyword_mira.html",
+            "keyword_tour.html",
+            "keywords.html",
+            "notes.html"
+        ]
+    );
+    // Removed keyword and category, with an incremental build
+    generate_text("*Mira* et *Général*\n", Some(&BTreeSet::new()));
+    assert_eq!(
+        pages(),
+        vec![
+            "index.html",
+            "keyword_general.html",
+            "keyword_mira.html",
+            "keywords.html",
+            "notes.html"
+        ]
+    );
+    fs::remove_dir_all(&root).unwrap();
+}